@@ -1,63 +1,1947 @@
+use std::collections::HashMap;
+
+use crate::diff::{self, LineMap, LinePosition, Side};
+use crate::git;
 use crate::github_api;
-use crate::prompt_utils::PromptPart;
+use crate::markdown;
+use crate::prompt_utils::{self, Priority, PromptPart};
+use crate::template;
+use crate::zed;
 
-pub fn pr_data(
+/// Number of commits listed when the local checkout diverges from the PR
+const MAX_DIVERGENT_COMMITS: usize = 20;
+
+/// What GitHub knows about a pull request, shared by the ways it is rendered
+struct PullRequestData {
+    pull_request: github_api::PullRequest,
+    reviews: Vec<github_api::Review>,
+    comments: Vec<github_api::PullRequestComment>,
+    /// Whether the thread of a review comment is resolved, by comment id
+    resolved_comments: HashMap<u32, bool>,
+}
+
+fn fetch_pull_request_data(
     owner: &str,
     repo: &str,
     pr_number: u32,
     config: &crate::config::Config,
-) -> Result<Vec<PromptPart>, String> {
-    // Use the github_api::get_github_pull_request function
+) -> Result<PullRequestData, String> {
     let pull_request = github_api::get_github_pull_request(owner, repo, pr_number, config)
         .map_err(|e| format!("Error fetching PR: {}", e))?;
+    let comments = github_api::get_github_pr_comments(owner, repo, pr_number, config)
+        .map_err(|e| format!("Error fetching PR comments: {}", e))?;
+    let reviews = github_api::get_github_pr_reviews(owner, repo, pr_number, config)
+        .map_err(|e| format!("Error fetching PR reviews: {}", e))?;
+
+    // Resolution is only known to GraphQL, which needs a token. Without one
+    // every comment counts as unresolved.
+    let resolved_comments: HashMap<u32, bool> =
+        github_api::get_github_review_threads(owner, repo, pr_number, config)
+            .unwrap_or_default()
+            .into_iter()
+            .flat_map(|thread| {
+                thread
+                    .comments
+                    .into_iter()
+                    .filter_map(move |comment| Some((comment.id?, thread.is_resolved)))
+            })
+            .collect();
+
+    Ok(PullRequestData {
+        pull_request,
+        reviews,
+        comments,
+        resolved_comments,
+    })
+}
+
+/// A rendered review comment, with what's needed to place it in its thread
+struct CommentPart {
+    path: String,
+    thread_id: u32,
+    thread_label: String,
+    /// The thread template before and after `{comments}`
+    thread_start: String,
+    thread_end: String,
+    part: PromptPart,
+}
+
+/// Details and review comments of a pull request. With the worktree the PR
+/// is checked out in, the local checkout is compared against the PR head.
+pub fn pr_data(
+    owner: &str,
+    repo: &str,
+    pr_number: u32,
+    config: &crate::config::Config,
+    worktree: Option<&zed::Worktree>,
+) -> Result<Vec<PromptPart>, String> {
+    let PullRequestData {
+        pull_request,
+        reviews,
+        comments,
+        resolved_comments,
+    } = fetch_pull_request_data(owner, repo, pr_number, config)?;
 
     // Convert the pull request to a PromptPart
-    let content = format!(
-        "\nPR #{}: {}\n\n{}\n",
-        pull_request.number,
-        pull_request.title,
-        pull_request
-            .body
-            .unwrap_or_else(|| "No description provided.".to_string())
+    let state = pull_request.display_state().to_uppercase();
+    let body = pull_request
+        .body
+        .as_deref()
+        .map(|body| markdown::normalize_body(body, Some((owner, repo)), config))
+        .filter(|body| !body.is_empty())
+        .unwrap_or_else(|| "No description provided.".to_string());
+    let content = template::render(
+        &config.templates.pull_request,
+        &[
+            ("number", &pull_request.number.to_string()),
+            ("title", &pull_request.title),
+            ("state", &state),
+            ("author", &pull_request.user.login),
+            ("head", &pull_request.head_ref),
+            ("base", &pull_request.base_ref),
+            ("url", &pull_request.html_url),
+            ("created", &pull_request.created_at),
+            ("body", &body),
+        ],
     );
 
     let pr_prompt_part = PromptPart {
-        length: content.len(),
-        label: format!("PR #{}: {}\n", pull_request.number, pull_request.title),
+        priority: Priority::Description,
+        label: format!(
+            "PR #{}: {} [{}]\n",
+            pull_request.number, pull_request.title, state
+        ),
         content,
+        children: Vec::new(),
     };
 
-    // Fetch comments
-    let comments =
-        github_api::get_github_pr_comments(owner, repo, pr_number.try_into().unwrap(), config)
-            .map_err(|e| format!("Error fetching PR comments: {}", e))?;
-
     // Convert comments to a vector of PromptPart
     let mut combined_parts = vec![pr_prompt_part];
 
+    let cwd = worktree.map(|worktree| worktree.root_path());
+    if let Some(cwd) = &cwd {
+        combined_parts.extend(divergence_part(cwd, &pull_request));
+    }
+
+    // Reviews without a verdict or summary only wrap their inline comments
+    let review_parts: Vec<PromptPart> = reviews
+        .iter()
+        .filter(|review| review.state != "PENDING")
+        .filter(|review| review.state != "COMMENTED" || !review.body.trim().is_empty())
+        .map(|review| review_part(review, owner, repo, config))
+        .collect();
+    if !review_parts.is_empty() {
+        combined_parts.push(PromptPart::group(
+            format!("Reviews ({})", review_parts.len()),
+            review_parts,
+        ));
+    }
+
+    // Comments on commits missing from the local history may not match the code,
+    // many comments share a commit so only ask git once per commit
+    let mut stale_commits: HashMap<String, bool> = HashMap::new();
+    // Diffs against the worktree are shared by the comments on a file and commit
+    let mut line_maps: HashMap<(String, String), Option<LineMap>> = HashMap::new();
+    let mut worktree_files: HashMap<String, Option<String>> = HashMap::new();
+
     // Add comment parts to the combined vector
-    let comment_parts: Vec<PromptPart> = comments
+    let comment_parts: Vec<CommentPart> = comments
         .into_iter()
         .map(|comment| {
-            let content = format!(
-                "\nComment from user: {}\n```diff\n{}\n```\n\n{}\n",
-                comment.user.login, comment.diff_hunk, comment.body
+            let stale = cwd.as_deref().is_some_and(|cwd| {
+                let commit_id = &comment.commit_id;
+                *stale_commits
+                    .entry(commit_id.clone())
+                    .or_insert_with(|| git::is_ancestor(cwd, commit_id, "HEAD") != Some(true))
+            });
+            let stale_note = if stale {
+                format!(
+                    "\nNote: this comment is on commit {}, which is not in your local history. The feedback may be stale.\n",
+                    short_sha(&comment.commit_id)
+                )
+            } else {
+                String::new()
+            };
+
+            let side = Side::from_github(comment.side.as_deref());
+            let start_side = comment_start_side(&comment);
+            let range = commented_range(&comment);
+            // Line numbers only compare within a side, so a range starting on
+            // the other side is narrowed to its last line
+            let side_range = match range {
+                Some((_, end)) if start_side != side => Some((end, end)),
+                range => range,
+            };
+
+            let review_position = cwd.as_deref().and_then(|cwd| {
+                // Comments on deleted lines have no line in the worktree
+                if side == Side::Old {
+                    return None;
+                }
+                // Outdated comments only have a line in the commit they were made on
+                let commit_id = if comment.line.is_some() {
+                    &comment.commit_id
+                } else {
+                    &comment.original_commit_id
+                };
+                let (_, line) = range?;
+                // `None` when the commit is missing locally and can't be diffed
+                let position = line_maps
+                    .entry((commit_id.clone(), comment.path.clone()))
+                    .or_insert_with(|| {
+                        git::diff_against_worktree(cwd, commit_id, &comment.path)
+                            .ok()
+                            .map(|diff| LineMap::from_diff(&diff))
+                    })
+                    .as_ref()
+                    .map(|line_map| line_map.map_line(line));
+                Some((line, position))
+            });
+            let location = match review_position {
+                Some((line, Some(position))) => format!(
+                    "Location in worktree: {}\n",
+                    local_position(&comment.path, line, position)
+                ),
+                Some((line, None)) => format!(
+                    "Location in worktree: {}:{} (line at review time, the review commit is missing locally)\n",
+                    comment.path, line
+                ),
+                None => String::new(),
+            };
+            // Falls back to the line at review time when it can't be mapped
+            let worktree_line = match review_position {
+                Some((
+                    _,
+                    Some(LinePosition::Unchanged(line) | LinePosition::Changed(line)),
+                )) => Some(line),
+                Some((line, None)) => Some(line),
+                _ => None,
+            };
+
+            // The hunk ends at the last commented line, so it holds the
+            // lines the comment is on as the reviewer saw them
+            let commented_lines: Vec<&str> = side_range
+                .map(|(start, end)| {
+                    diff::side_lines(&comment.diff_hunk, side)
+                        .into_iter()
+                        .filter(|(line, _)| (start..=end).contains(line))
+                        .map(|(_, text)| text)
+                        .collect()
+                })
+                .unwrap_or_default();
+
+            // Show which lines of the hunk a multi-line comment covers
+            let commented_code = match side_range {
+                Some((start, end)) if start < end && comment.in_reply_to_id == 0 => format!(
+                    "Commented lines:\n```{}\n{}\n```\n",
+                    prompt_utils::fence_language(&comment.path),
+                    commented_lines.join("\n")
+                ),
+                _ => String::new(),
+            };
+
+            let file = match (worktree, worktree_line) {
+                (Some(worktree), Some(_)) => worktree_files
+                    .entry(comment.path.clone())
+                    .or_insert_with(|| worktree.read_text_file(&comment.path).ok())
+                    .as_deref(),
+                _ => None,
+            };
+            // Commented lines in the worktree, assuming the range moved as a whole
+            let worktree_range = worktree_line.map(|line| {
+                let range_lines = side_range.map(|(start, end)| end - start).unwrap_or(0);
+                (line.saturating_sub(range_lines).max(1), line)
+            });
+
+            // Replies share the thread's location, so only show the code once
+            let current_code = match (file, worktree_range) {
+                (Some(file), Some((first, last))) if comment.in_reply_to_id == 0 => {
+                    file_window(&comment.path, file, first, last, config.file_context_lines)
+                }
+                _ => None,
+            }
+            .unwrap_or_default();
+
+            // Suggestions replace the commented lines, so they are left out
+            // when the hunk doesn't hold all of those lines
+            let suggestions = diff::parse_suggestions(&comment.body);
+            let suggestions = match range {
+                _ if suggestions.is_empty() => String::new(),
+                Some(_) if start_side != side => {
+                    "Suggested change left out, the commented lines start on removed lines.\n"
+                        .to_string()
+                }
+                Some((start, end))
+                    if side == Side::New
+                        && commented_lines.len() == (end - start + 1) as usize =>
+                {
+                    suggestions
+                        .iter()
+                        .map(|suggestion| {
+                            suggestion_part(
+                                &comment,
+                                start,
+                                &commented_lines,
+                                suggestion,
+                                file.zip(worktree_range),
+                            )
+                        })
+                        .collect::<String>()
+                }
+                _ => "Suggested change left out, the diff hunk doesn't hold the commented lines.\n"
+                    .to_string(),
+            };
+
+            // GitHub's hunk runs from the top of the diff hunk to the comment.
+            // A range across both sides is kept whole, as it can't be told
+            // where it starts on one side.
+            let diff_hunk = match range {
+                Some((start, end)) if start_side == side => diff::trim_hunk(
+                    &comment.diff_hunk,
+                    side,
+                    start,
+                    end,
+                    config.hunk_context_lines,
+                ),
+                _ => comment.diff_hunk.clone(),
+            };
+
+            let review_at = review_location(
+                &comment.path,
+                range,
+                (start_side, side),
+                comment.line.is_none(),
             );
+            let content = template::render(
+                &config.templates.comment,
+                &[
+                    ("author", &comment.user.login),
+                    ("location", &review_at),
+                    ("worktree_location", &location),
+                    ("url", &comment.html_url),
+                    ("diff", &diff_hunk),
+                    ("commented_code", &commented_code),
+                    ("current_code", &current_code),
+                    ("stale_note", &stale_note),
+                    (
+                        "body",
+                        &markdown::normalize_body(&comment.body, Some((owner, repo)), config),
+                    ),
+                    ("suggestions", &suggestions),
+                ],
+            );
+            let thread_id = match comment.in_reply_to_id {
+                0 => comment.id,
+                id => id,
+            };
+            // Threads are resolved as a whole, and their first comment is
+            // always among the comments GraphQL returns for them
+            let resolved = resolved_comments.get(&thread_id).copied().unwrap_or(false);
+            let stale_label = match (stale, resolved) {
+                (true, true) => " (stale, resolved)",
+                (true, false) => " (stale)",
+                (false, true) => " (resolved)",
+                (false, false) => "",
+            };
             let label = if comment.in_reply_to_id != 0 {
-                format!("↪ Reply to comment by @{}", comment.user.login)
+                format!("↪ Reply to comment by @{}{}", comment.user.login, stale_label)
             } else {
-                format!("Comment by @{}", comment.user.login)
+                format!("Comment by @{}{}", comment.user.login, stale_label)
+            };
+            let priority = if comment.user.is_bot {
+                Priority::Bot
+            } else if resolved {
+                Priority::ResolvedThread
+            } else {
+                Priority::UnresolvedThread
             };
 
-            PromptPart {
-                length: content.len(),
+            let thread_label = format!(
+                "{}{}",
+                match range {
+                    Some((start, end)) if start < end => format!("Thread at L{}-L{}", start, end),
+                    Some((_, line)) => format!("Thread at L{}", line),
+                    None => "Thread on file".to_string(),
+                },
+                if resolved { " (resolved)" } else { "" }
+            );
+
+            // The thread template wraps the thread's comments, which are
+            // sections of their own
+            let line = range.map(|(_, end)| end.to_string()).unwrap_or_default();
+            let thread_values = [
+                ("location", review_at.as_str()),
+                ("path", comment.path.as_str()),
+                ("line", line.as_str()),
+            ];
+            let (thread_start, thread_end) = config
+                .templates
+                .thread
+                .split_once("{comments}")
+                .unwrap_or((&config.templates.thread, ""));
+
+            let part = PromptPart {
+                priority,
                 label,
                 content,
+                children: Vec::new(),
+            };
+            CommentPart {
+                path: comment.path.clone(),
+                thread_id,
+                thread_label,
+                thread_start: template::render(thread_start, &thread_values),
+                thread_end: template::render(thread_end, &thread_values),
+                part,
             }
         })
         .collect();
 
-    combined_parts.extend(comment_parts);
+    // Files and threads in the order they were first commented on, the
+    // first comment of a thread names it. Replies are nested under that
+    // comment, so they are folded and budgeted along with it.
+    let mut file_parts: Vec<PromptPart> = Vec::new();
+    let mut thread_positions: HashMap<u32, (usize, usize)> = HashMap::new();
+    let mut thread_ends: Vec<(usize, usize, PromptPart)> = Vec::new();
+    for CommentPart {
+        path,
+        thread_id,
+        thread_label,
+        thread_start,
+        thread_end,
+        mut part,
+    } in comment_parts
+    {
+        if let Some(&(file, thread)) = thread_positions.get(&thread_id) {
+            file_parts[file].children[thread].children[0]
+                .children
+                .push(part);
+            continue;
+        }
+        // The start of the thread template heads its first comment, and the
+        // end follows the last reply, so they are kept along with the comment
+        part.content.insert_str(0, &thread_start);
+        let end = PromptPart {
+            priority: part.priority,
+            label: "End of thread".to_string(),
+            content: thread_end,
+            children: Vec::new(),
+        };
+
+        let file = file_group(&mut file_parts, &path);
+        let threads = &mut file_parts[file].children;
+        threads.push(PromptPart::group(thread_label, vec![part]));
+        thread_positions.insert(thread_id, (file, threads.len() - 1));
+        if !end.content.trim().is_empty() {
+            thread_ends.push((file, threads.len() - 1, end));
+        }
+    }
+    for (file, thread, end) in thread_ends {
+        file_parts[file].children[thread].children[0]
+            .children
+            .push(end);
+    }
+
+    if !file_parts.is_empty() {
+        combined_parts.push(PromptPart::group("Review comments".to_string(), file_parts));
+    }
 
     Ok(combined_parts)
 }
+
+/// Action items left on a pull request as a checklist: failing checks,
+/// requested changes, then unresolved review threads grouped by file
+pub fn pr_checklist_data(
+    owner: &str,
+    repo: &str,
+    pr_number: u32,
+    config: &crate::config::Config,
+) -> Result<Vec<PromptPart>, String> {
+    let PullRequestData {
+        pull_request,
+        reviews,
+        comments,
+        resolved_comments,
+    } = fetch_pull_request_data(owner, repo, pr_number, config)?;
+
+    let resolution_note = if config.github_token.is_none() && !comments.is_empty() {
+        "Without GITHUB_TOKEN resolved threads can't be told apart, so every thread is listed.\n"
+    } else {
+        ""
+    };
+    let content = format!(
+        "\nAction items for PR #{}: {} [{}]\n{}\n{}",
+        pull_request.number,
+        pull_request.title,
+        pull_request.display_state().to_uppercase(),
+        pull_request.html_url,
+        resolution_note
+    );
+    let mut combined_parts = vec![PromptPart {
+        priority: Priority::Description,
+        label: format!(
+            "Action items for PR #{}: {}",
+            pull_request.number, pull_request.title
+        ),
+        content,
+        children: Vec::new(),
+    }];
+
+    // The PR can't be merged while checks fail, so they come first. The
+    // rest of the list is still useful when they can't be fetched.
+    let checks = match github_api::get_github_checks(owner, repo, &pull_request.head_sha, config) {
+        Ok(checks) => checks,
+        Err(e) => {
+            combined_parts.push(PromptPart {
+                priority: Priority::Description,
+                label: "Checks could not be fetched".to_string(),
+                content: format!("\nChecks could not be fetched: {}\n", e),
+                children: Vec::new(),
+            });
+            Vec::new()
+        }
+    };
+    let check_items: Vec<PromptPart> = checks
+        .iter()
+        .filter(|check| check.is_failing())
+        .map(|check| {
+            let result = match &check.description {
+                Some(description) => format!(
+                    "{}: {}",
+                    check.result.as_deref().unwrap_or_default(),
+                    description
+                ),
+                None => check.result.clone().unwrap_or_default(),
+            };
+            checklist_item(
+                format!("Check {}", check.name),
+                &format!("Fix failing check `{}` ({})", check.name, result),
+                "",
+                check.html_url.as_deref(),
+                Priority::Description,
+            )
+        })
+        .collect();
+    if !check_items.is_empty() {
+        combined_parts.push(PromptPart::group(
+            format!("Failing checks ({})", check_items.len()),
+            check_items,
+        ));
+    }
+
+    // Only the latest verdict of each reviewer counts, comments don't change it
+    let mut verdicts: Vec<&github_api::Review> = Vec::new();
+    for review in reviews
+        .iter()
+        .filter(|review| review.state != "COMMENTED" && review.state != "PENDING")
+    {
+        verdicts.retain(|verdict| verdict.user.login != review.user.login);
+        verdicts.push(review);
+    }
+    let review_items: Vec<PromptPart> = verdicts
+        .into_iter()
+        .filter(|review| review.state == "CHANGES_REQUESTED")
+        .map(|review| {
+            checklist_item(
+                format!("Changes requested by @{}", review.user.login),
+                &format!("Address the changes requested by @{}", review.user.login),
+                &markdown::normalize_body(&review.body, Some((owner, repo)), config),
+                Some(&review.html_url),
+                Priority::Review,
+            )
+        })
+        .collect();
+    if !review_items.is_empty() {
+        combined_parts.push(PromptPart::group(
+            format!("Changes requested ({})", review_items.len()),
+            review_items,
+        ));
+    }
+
+    // Unresolved threads, by file in the order they were first commented on
+    let mut file_parts: Vec<PromptPart> = Vec::new();
+    for comment in comments.iter().filter(|comment| {
+        comment.in_reply_to_id == 0 && resolved_comments.get(&comment.id) != Some(&true)
+    }) {
+        let replies: Vec<&github_api::PullRequestComment> = comments
+            .iter()
+            .filter(|reply| reply.in_reply_to_id == comment.id)
+            .collect();
+        let mut details = markdown::normalize_body(&comment.body, Some((owner, repo)), config);
+        if let Some(last) = replies.last() {
+            details.push_str(&format!(
+                "\n\n{} {}, the last by @{}",
+                replies.len(),
+                if replies.len() == 1 {
+                    "reply"
+                } else {
+                    "replies"
+                },
+                last.user.login
+            ));
+        }
+
+        let location = review_location(
+            &comment.path,
+            commented_range(comment),
+            (
+                comment_start_side(comment),
+                Side::from_github(comment.side.as_deref()),
+            ),
+            comment.line.is_none(),
+        );
+        let item = checklist_item(
+            format!("@{} on {}", comment.user.login, location),
+            &format!("{} (@{})", location, comment.user.login),
+            &details,
+            Some(&comment.html_url),
+            if comment.user.is_bot {
+                Priority::Bot
+            } else {
+                Priority::UnresolvedThread
+            },
+        );
+
+        let file = file_group(&mut file_parts, &comment.path);
+        file_parts[file].children.push(item);
+    }
+    if !file_parts.is_empty() {
+        combined_parts.push(PromptPart::group(
+            "Unresolved threads".to_string(),
+            file_parts,
+        ));
+    }
+
+    if combined_parts.len() == 1 {
+        combined_parts[0]
+            .content
+            .push_str("\nNo open action items.\n");
+    }
+
+    Ok(combined_parts)
+}
+
+/// Index of the group for `path` in `file_parts`, added at the end when
+/// the file has no group yet
+fn file_group(file_parts: &mut Vec<PromptPart>, path: &str) -> usize {
+    match file_parts.iter().position(|file| file.label == path) {
+        Some(file) => file,
+        None => {
+            file_parts.push(PromptPart::group(path.to_string(), Vec::new()));
+            file_parts.len() - 1
+        }
+    }
+}
+
+/// A `- [ ]` item with its details and link indented below it
+fn checklist_item(
+    label: String,
+    title: &str,
+    details: &str,
+    url: Option<&str>,
+    priority: Priority,
+) -> PromptPart {
+    let mut content = format!("- [ ] {}\n", title);
+    for line in details.lines().chain(url) {
+        if line.trim().is_empty() {
+            content.push('\n');
+        } else {
+            content.push_str(&format!("  {}\n", line));
+        }
+    }
+
+    PromptPart {
+        priority,
+        label,
+        content,
+        children: Vec::new(),
+    }
+}
+
+fn review_part(
+    review: &github_api::Review,
+    owner: &str,
+    repo: &str,
+    config: &crate::config::Config,
+) -> PromptPart {
+    let verdict = match review.state.as_str() {
+        "APPROVED" => "approved",
+        "CHANGES_REQUESTED" => "requested changes",
+        "DISMISSED" => "dismissed review",
+        _ => "reviewed",
+    };
+    let body = markdown::normalize_body(&review.body, Some((owner, repo)), config);
+    let content = template::render(
+        &config.templates.review,
+        &[
+            ("author", &review.user.login),
+            ("verdict", verdict),
+            ("state", &review.state),
+            ("url", &review.html_url),
+            (
+                "submitted",
+                review.submitted_at.as_deref().unwrap_or_default(),
+            ),
+            (
+                "body",
+                if body.is_empty() {
+                    "No summary provided."
+                } else {
+                    &body
+                },
+            ),
+        ],
+    );
+
+    PromptPart {
+        priority: if review.user.is_bot {
+            Priority::Bot
+        } else {
+            Priority::Review
+        },
+        label: format!("Review by @{}: {}", review.user.login, verdict),
+        content,
+        children: Vec::new(),
+    }
+}
+
+/// Renders `path:line` for the current worktree, noting lines changed since the review
+fn local_position(path: &str, review_line: u32, position: LinePosition) -> String {
+    match position {
+        LinePosition::Unchanged(line) if line == review_line => format!("{}:{}", path, line),
+        LinePosition::Unchanged(line) => {
+            format!("{}:{} (line {} at review time)", path, line, review_line)
+        }
+        LinePosition::Changed(line) => {
+            format!("{}:{} (changed locally since the review)", path, line)
+        }
+        LinePosition::Deleted => format!(
+            "{}:{} (deleted locally since the review)",
+            path, review_line
+        ),
+    }
+}
+
+/// Number of context lines around suggested changes, as `git diff` uses
+const SUGGESTION_CONTEXT_LINES: usize = 3;
+
+/// Renders a suggested change as a patch. Context lines come from the
+/// worktree file when the commented lines are still there, so the patch
+/// applies with `git apply`; otherwise only the lines above the comment in
+/// its diff hunk are known, and the patch needs `git apply --unidiff-zero`.
+fn suggestion_part(
+    comment: &github_api::PullRequestComment,
+    start: u32,
+    commented_lines: &[&str],
+    suggestion: &str,
+    file: Option<(&str, (u32, u32))>,
+) -> String {
+    let worktree_context = file.and_then(|(file, (first, _))| {
+        let lines: Vec<&str> = file.lines().collect();
+        let first_index = (first as usize).checked_sub(1)?;
+        let end = first_index + commented_lines.len();
+        if lines.get(first_index..end)? != commented_lines {
+            return None;
+        }
+        let before = &lines[first_index.saturating_sub(SUGGESTION_CONTEXT_LINES)..first_index];
+        let after = &lines[end..(end + SUGGESTION_CONTEXT_LINES).min(lines.len())];
+        Some((first, before.to_vec(), after.to_vec()))
+    });
+
+    match worktree_context {
+        Some((first, before, after)) => format!(
+            "Suggested change (apply with `git apply`):\n```diff\n{}\n```\n",
+            diff::suggestion_patch(
+                &comment.path,
+                first,
+                &before,
+                commented_lines,
+                suggestion,
+                &after
+            )
+        ),
+        None => {
+            let hunk_lines = diff::side_lines(&comment.diff_hunk, Side::New);
+            let before: Vec<&str> = hunk_lines
+                .iter()
+                .filter(|(line, _)| *line < start)
+                .map(|(_, text)| *text)
+                .collect();
+            let before = &before[before.len().saturating_sub(SUGGESTION_CONTEXT_LINES)..];
+            format!(
+                "Suggested change (apply with `git apply --unidiff-zero`):\n```diff\n{}\n```\n",
+                diff::suggestion_patch(
+                    &comment.path,
+                    start,
+                    before,
+                    commented_lines,
+                    suggestion,
+                    &[]
+                )
+            )
+        }
+    }
+}
+
+/// Lines of the current file around `first..=last`, with those lines marked,
+/// so the code can be seen as it is now next to the hunk the reviewer saw
+fn file_window(
+    path: &str,
+    file: &str,
+    first: u32,
+    last: u32,
+    context_lines: usize,
+) -> Option<String> {
+    let lines: Vec<&str> = file.lines().collect();
+    let index = (last as usize)
+        .checked_sub(1)
+        .filter(|i| *i < lines.len())?;
+    let first_index = (first as usize).saturating_sub(1).min(index);
+    let start = first_index.saturating_sub(context_lines);
+    let end = (index + context_lines + 1).min(lines.len());
+
+    let width = end.to_string().len();
+    let code = lines[start..end]
+        .iter()
+        .enumerate()
+        .map(|(offset, text)| {
+            let number = start + offset + 1;
+            let marker = if (first_index + 1..=index + 1).contains(&number) {
+                ">"
+            } else {
+                " "
+            };
+            format!("{:>width$} {} {}", number, marker, text, width = width)
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    Some(format!(
+        "Current code in worktree ({}:{}-{}):\n```{}\n{}\n```\n",
+        path,
+        start + 1,
+        end,
+        prompt_utils::fence_language(path),
+        code
+    ))
+}
+
+/// First and last line a review comment is on, at the commit it was made on
+/// when outdated. `None` for comments on a whole file.
+fn commented_range(comment: &github_api::PullRequestComment) -> Option<(u32, u32)> {
+    let (start, end) = match comment.line {
+        Some(line) => (comment.start_line, line),
+        None => (comment.original_start_line, comment.original_line?),
+    };
+    // Lines on different sides don't compare
+    let start = match start {
+        Some(start)
+            if comment_start_side(comment) != Side::from_github(comment.side.as_deref()) =>
+        {
+            start
+        }
+        start => start.unwrap_or(end).min(end),
+    };
+    Some((start, end))
+}
+
+/// Side of the first line of a review comment, the side of the comment
+/// unless it's a multi-line comment starting on the other side
+fn comment_start_side(comment: &github_api::PullRequestComment) -> Side {
+    match comment.start_side.as_deref() {
+        Some(start_side) => Side::from_github(Some(start_side)),
+        None => Side::from_github(comment.side.as_deref()),
+    }
+}
+
+/// Renders where a review comment is, e.g. `src/lib.rs:10-14`. `sides` are
+/// the sides of the first and last commented lines.
+fn review_location(
+    path: &str,
+    range: Option<(u32, u32)>,
+    (start_side, side): (Side, Side),
+    outdated: bool,
+) -> String {
+    let mut location = match (range, start_side, side) {
+        (Some((start, end)), Side::Old, Side::New) => {
+            format!("{}: removed line {} to line {}", path, start, end)
+        }
+        (Some((start, end)), Side::New, Side::Old) => {
+            format!("{}: line {} to removed line {}", path, start, end)
+        }
+        (Some((start, end)), _, _) if start < end => format!("{}:{}-{}", path, start, end),
+        (Some((_, line)), _, _) => format!("{}:{}", path, line),
+        (None, _, _) => format!("{} (whole file)", path),
+    };
+    if range.is_some() && start_side == Side::Old && side == Side::Old {
+        location.push_str(" (removed lines)");
+    }
+    if outdated {
+        location.push_str(" (outdated, lines at review time)");
+    }
+    location
+}
+
+fn short_sha(sha: &str) -> &str {
+    &sha[..7.min(sha.len())]
+}
+
+/// Compares local HEAD and the upstream ref against the PR head, so the
+/// assistant knows when review comments may not match the local code
+fn divergence_part(cwd: &str, pull_request: &github_api::PullRequest) -> Option<PromptPart> {
+    let local_head = git::get_head_sha(cwd).ok()?;
+    let pr_head = &pull_request.head_sha;
+
+    let mut content = format!(
+        "\nLocal checkout: HEAD is at {}, the PR head is at {}.\n",
+        short_sha(&local_head),
+        short_sha(pr_head)
+    );
+    let in_sync = if local_head == *pr_head {
+        content.push_str("Local HEAD matches the PR head.\n");
+        true
+    } else if git::resolve_commit(cwd, pr_head).is_none() {
+        content.push_str(
+            "The PR head is missing locally, it was pushed or force-pushed since your last fetch. Run `git fetch` to get it.\n",
+        );
+        false
+    } else {
+        let list = |commits: Vec<String>| {
+            let commits = commits
+                .iter()
+                .map(|commit| format!("- {}", commit))
+                .collect::<Vec<_>>()
+                .join("\n");
+            prompt_utils::truncate_lines(&commits, MAX_DIVERGENT_COMMITS)
+        };
+
+        let unpushed = git::log_oneline(cwd, &format!("{}..HEAD", pr_head)).unwrap_or_default();
+        if !unpushed.is_empty() {
+            content.push_str(&format!(
+                "{} local commits are not pushed to the PR:\n{}\n",
+                unpushed.len(),
+                list(unpushed)
+            ));
+        }
+
+        let behind = git::log_oneline(cwd, &format!("HEAD..{}", pr_head)).unwrap_or_default();
+        if !behind.is_empty() {
+            content.push_str(&format!(
+                "Local HEAD is {} commits behind the PR head:\n{}\n",
+                behind.len(),
+                list(behind)
+            ));
+        }
+        false
+    };
+
+    if let Some(upstream) = git::resolve_commit(cwd, "@{upstream}") {
+        if upstream != *pr_head {
+            content.push_str(&format!(
+                "The upstream tracking ref is at {}, which is not the PR head.\n",
+                short_sha(&upstream)
+            ));
+        }
+    }
+
+    Some(PromptPart {
+        priority: Priority::Description,
+        label: if in_sync {
+            "Local checkout matches the PR".to_string()
+        } else {
+            "Local checkout differs from the PR".to_string()
+        },
+        content,
+        children: Vec::new(),
+    })
+}
+
+pub fn compare_data(
+    owner: &str,
+    repo: &str,
+    base: &str,
+    head: &str,
+    config: &crate::config::Config,
+) -> Result<Vec<PromptPart>, String> {
+    let comparison = github_api::get_github_comparison(owner, repo, base, head, config)
+        .map_err(|e| format!("Error fetching comparison: {}", e))?;
+
+    let content = format!(
+        "\nComparing {}...{} in {}/{} ({})\n\n{} is {} commits ahead and {} commits behind {}.\n{} files changed.\n",
+        base,
+        head,
+        owner,
+        repo,
+        comparison.html_url,
+        head,
+        comparison.ahead_by,
+        comparison.behind_by,
+        base,
+        comparison.files.len()
+    );
+
+    let mut combined_parts = vec![PromptPart {
+        priority: Priority::Description,
+        label: format!("Compare {}...{} ({})", base, head, comparison.status),
+        content,
+        children: Vec::new(),
+    }];
+
+    if !comparison.commits.is_empty() {
+        let mut content = String::from("\nCommits:\n");
+        for commit in &comparison.commits {
+            let summary = commit.message.lines().next().unwrap_or_default();
+            content.push_str(&format!(
+                "- {} {} (@{})\n",
+                short_sha(&commit.sha),
+                summary,
+                commit.author
+            ));
+        }
+        // The compare API only returns the first 250 commits
+        if comparison.total_commits as usize > comparison.commits.len() {
+            content.push_str(&format!(
+                "... and {} more commits\n",
+                comparison.total_commits as usize - comparison.commits.len()
+            ));
+        }
+
+        combined_parts.push(PromptPart {
+            priority: Priority::Description,
+            label: format!("Commits ({})", comparison.total_commits),
+            content,
+            children: Vec::new(),
+        });
+    }
+
+    // Only truncate patches when the whole comparison doesn't fit the budget,
+    // then share the budget evenly between the files
+    let total_patch_lines: usize = comparison
+        .files
+        .iter()
+        .filter_map(|file| file.patch.as_ref())
+        .map(|patch| patch.lines().count())
+        .sum();
+    let max_lines_per_file = if total_patch_lines > config.max_patch_lines {
+        Some((config.max_patch_lines / comparison.files.len().max(1)).max(10))
+    } else {
+        None
+    };
+
+    let file_parts: Vec<PromptPart> = comparison
+        .files
+        .into_iter()
+        .map(|file| {
+            let patch = match (&file.patch, max_lines_per_file) {
+                (Some(patch), Some(max_lines)) => prompt_utils::truncate_lines(patch, max_lines),
+                (Some(patch), None) => patch.clone(),
+                (None, _) => "No patch available (binary or too large).".to_string(),
+            };
+            let content = format!(
+                "\nFile: {} ({}, +{} -{})\n```diff\n{}\n```\n",
+                file.filename, file.status, file.additions, file.deletions, patch
+            );
+
+            PromptPart {
+                priority: Priority::Description,
+                label: format!(
+                    "{} (+{} -{})",
+                    file.filename, file.additions, file.deletions
+                ),
+                content,
+                children: Vec::new(),
+            }
+        })
+        .collect();
+
+    combined_parts.extend(file_parts);
+
+    Ok(combined_parts)
+}
+
+/// Number of releases shown when no range is given
+const DEFAULT_RELEASE_COUNT: usize = 10;
+
+fn release_part(
+    release: &github_api::Release,
+    owner: &str,
+    repo: &str,
+    config: &crate::config::Config,
+) -> PromptPart {
+    let kind = if release.prerelease {
+        " (prerelease)"
+    } else {
+        ""
+    };
+    let content = format!(
+        "\nRelease {}{}: {}\nPublished: {}\n{}\n\n{}\n",
+        release.tag_name,
+        kind,
+        release.name.as_deref().unwrap_or(&release.tag_name),
+        release.published_at.as_deref().unwrap_or("unpublished"),
+        release.html_url,
+        release
+            .body
+            .as_deref()
+            .map(|body| markdown::normalize_body(body, Some((owner, repo)), config))
+            .filter(|body| !body.is_empty())
+            .as_deref()
+            .unwrap_or("No release notes provided.")
+    );
+
+    PromptPart {
+        priority: Priority::Description,
+        label: format!("Release {}{}", release.tag_name, kind),
+        content,
+        children: Vec::new(),
+    }
+}
+
+/// Releases after `from` up to and including `to`, oldest first.
+/// Without a range the latest releases are returned.
+pub fn release_data(
+    owner: &str,
+    repo: &str,
+    from: Option<&str>,
+    to: Option<&str>,
+    config: &crate::config::Config,
+) -> Result<Vec<PromptPart>, String> {
+    // Releases are returned newest first, drafts have no tag to compare against
+    // yet. Pages are fetched until the oldest release needed has been seen.
+    let mut releases: Vec<github_api::Release> = Vec::new();
+    for page in 1.. {
+        let page_releases = github_api::get_github_releases(owner, repo, page, config)
+            .map_err(|e| format!("Error fetching releases: {}", e))?;
+        let last_page = page_releases.len() < github_api::RELEASES_PER_PAGE;
+        releases.extend(page_releases.into_iter().filter(|r| !r.draft));
+
+        let complete = match (from, to) {
+            (Some(from), _) => releases.iter().any(|release| release.tag_name == from),
+            // Everything up to the first release
+            (None, Some(_)) => false,
+            (None, None) => releases.len() >= DEFAULT_RELEASE_COUNT,
+        };
+        if last_page || complete {
+            break;
+        }
+    }
+
+    let find_tag = |tag: &str| {
+        releases
+            .iter()
+            .position(|release| release.tag_name == tag)
+            .ok_or(format!("Release {} not found in {}/{}", tag, owner, repo))
+    };
+
+    let newest = match to {
+        Some(tag) => find_tag(tag)?,
+        None => 0,
+    };
+    let oldest = match from {
+        Some(tag) => find_tag(tag)?,
+        None if to.is_none() => releases.len().min(DEFAULT_RELEASE_COUNT),
+        None => releases.len(),
+    };
+
+    if newest >= oldest {
+        return Err(format!(
+            "No releases between {} and {}",
+            from.unwrap_or("the first release"),
+            to.unwrap_or("the latest release")
+        ));
+    }
+
+    let selected = &releases[newest..oldest];
+    let prerelease_count = selected.iter().filter(|r| r.prerelease).count();
+
+    let content = format!(
+        "\nReleases of {}/{} from {} to {}: {} releases ({} prereleases)\n",
+        owner,
+        repo,
+        selected[selected.len() - 1].tag_name,
+        selected[0].tag_name,
+        selected.len(),
+        prerelease_count
+    );
+
+    let mut combined_parts = vec![PromptPart {
+        priority: Priority::Description,
+        label: format!("Releases {}/{}", owner, repo),
+        content,
+        children: Vec::new(),
+    }];
+
+    combined_parts.extend(
+        selected
+            .iter()
+            .rev()
+            .map(|release| release_part(release, owner, repo, config)),
+    );
+
+    Ok(combined_parts)
+}
+
+pub fn single_release_data(
+    owner: &str,
+    repo: &str,
+    tag: &str,
+    config: &crate::config::Config,
+) -> Result<Vec<PromptPart>, String> {
+    let release = github_api::get_github_release_by_tag(owner, repo, tag, config)
+        .map_err(|e| format!("Error fetching release: {}", e))?;
+
+    Ok(vec![release_part(&release, owner, repo, config)])
+}
+
+fn discussion_comment_part(
+    comment: &github_api::DiscussionComment,
+    is_reply: bool,
+    owner: &str,
+    repo: &str,
+    config: &crate::config::Config,
+) -> PromptPart {
+    let answer = if comment.is_answer {
+        " (marked as answer)"
+    } else {
+        ""
+    };
+    let content = format!(
+        "\nComment from user: {}{}\n\n{}\n",
+        comment.author,
+        answer,
+        markdown::normalize_body(&comment.body, Some((owner, repo)), config)
+    );
+    let label = if is_reply {
+        format!("↪ Reply to comment by @{}", comment.author)
+    } else if comment.is_answer {
+        format!("✓ Answer by @{}", comment.author)
+    } else {
+        format!("Comment by @{}", comment.author)
+    };
+
+    PromptPart {
+        priority: Priority::Description,
+        label,
+        content,
+        children: Vec::new(),
+    }
+}
+
+pub fn discussion_data(
+    owner: &str,
+    repo: &str,
+    number: u32,
+    config: &crate::config::Config,
+) -> Result<Vec<PromptPart>, String> {
+    let discussion = github_api::get_github_discussion(owner, repo, number, config)
+        .map_err(|e| format!("Error fetching discussion: {}", e))?;
+
+    let body = markdown::normalize_body(&discussion.body, Some((owner, repo)), config);
+    let answered = discussion
+        .comments
+        .iter()
+        .any(|comment| comment.is_answer || comment.replies.iter().any(|reply| reply.is_answer));
+
+    let content = format!(
+        "\nDiscussion #{}: {}\nCategory: {}{}\nStarted by @{}\n\n{}\n",
+        discussion.number,
+        discussion.title,
+        discussion.category,
+        if answered { " (answered)" } else { "" },
+        discussion.author,
+        if body.is_empty() {
+            "No description provided."
+        } else {
+            &body
+        }
+    );
+
+    let mut combined_parts = vec![PromptPart {
+        priority: Priority::Description,
+        label: format!("Discussion #{}: {}", discussion.number, discussion.title),
+        content,
+        children: Vec::new(),
+    }];
+
+    // Replies are nested under the comment they belong to, so they are
+    // folded and budgeted along with it, as in PR review threads
+    let comment_parts: Vec<PromptPart> = discussion
+        .comments
+        .iter()
+        .map(|comment| {
+            let mut part = discussion_comment_part(comment, false, owner, repo, config);
+            part.children = comment
+                .replies
+                .iter()
+                .map(|reply| discussion_comment_part(reply, true, owner, repo, config))
+                .collect();
+            part
+        })
+        .collect();
+    if !comment_parts.is_empty() {
+        combined_parts.push(PromptPart::group(
+            format!("Comments ({})", comment_parts.len()),
+            comment_parts,
+        ));
+    }
+
+    Ok(combined_parts)
+}
+
+pub fn gist_data(
+    gist_id: &str,
+    revision: Option<&str>,
+    config: &crate::config::Config,
+) -> Result<Vec<PromptPart>, String> {
+    let gist = github_api::get_github_gist(gist_id, revision, config)
+        .map_err(|e| format!("Error fetching gist: {}", e))?;
+
+    let content = format!(
+        "\nGist {}{} by @{} ({})\n\n{}\n",
+        gist.id,
+        revision
+            .map(|revision| format!(" at revision {}", short_sha(revision)))
+            .unwrap_or_default(),
+        gist.owner.as_deref().unwrap_or("anonymous"),
+        gist.html_url,
+        gist.description
+            .as_deref()
+            .unwrap_or("No description provided.")
+    );
+
+    let mut combined_parts = vec![PromptPart {
+        priority: Priority::Description,
+        label: format!("Gist: {}", gist.description.as_deref().unwrap_or(&gist.id)),
+        content,
+        children: Vec::new(),
+    }];
+
+    let file_parts: Vec<PromptPart> = gist
+        .files
+        .iter()
+        .map(|file| {
+            let language = file
+                .language
+                .as_deref()
+                .unwrap_or_default()
+                .to_lowercase()
+                .replace(' ', "-");
+            let mut file_content =
+                prompt_utils::truncate_lines(&file.content, config.max_file_lines);
+            if file.truncated {
+                file_content.push_str("\n... (file truncated by GitHub)");
+            }
+            let content = format!(
+                "\nFile: {}\n```{}\n{}\n```\n",
+                file.filename, language, file_content
+            );
+
+            PromptPart {
+                priority: Priority::Description,
+                label: file.filename.clone(),
+                content,
+                children: Vec::new(),
+            }
+        })
+        .collect();
+
+    combined_parts.extend(file_parts);
+
+    if gist.comments > 0 {
+        let comments = github_api::get_github_gist_comments(gist_id, config)
+            .map_err(|e| format!("Error fetching gist comments: {}", e))?;
+
+        combined_parts.extend(comments.into_iter().map(|comment| {
+            let content = format!(
+                "\nComment from user: {}\n\n{}\n",
+                comment.user.login,
+                markdown::normalize_body(&comment.body, None, config)
+            );
+
+            PromptPart {
+                priority: Priority::Description,
+                label: format!("Comment by @{}", comment.user.login),
+                content,
+                children: Vec::new(),
+            }
+        }));
+    }
+
+    Ok(combined_parts)
+}
+
+/// Lists open pull requests where the authenticated user's review is requested
+pub fn review_requests_data(config: &crate::config::Config) -> Result<Vec<PromptPart>, String> {
+    let user = github_api::get_github_authenticated_user(config)
+        .map_err(|e| format!("Error fetching authenticated user: {}", e))?;
+
+    let pull_requests = github_api::get_github_review_requests(config)
+        .map_err(|e| format!("Error searching review requests: {}", e))?;
+
+    let content = format!(
+        "\nReview requests for @{}: {} open pull requests\n",
+        user.login,
+        pull_requests.len()
+    );
+
+    let mut combined_parts = vec![PromptPart {
+        priority: Priority::Description,
+        label: format!("Review requests for @{}", user.login),
+        content,
+        children: Vec::new(),
+    }];
+
+    let pr_parts: Vec<PromptPart> = pull_requests
+        .iter()
+        .map(|pr| {
+            let content = format!(
+                "\n{}/{}#{}: {}\nOpened by @{} {} ago, +{} -{} in {} files\n{}\n",
+                pr.repo_owner,
+                pr.repo_name,
+                pr.number,
+                pr.title,
+                pr.author,
+                prompt_utils::format_age(&pr.created_at),
+                pr.additions,
+                pr.deletions,
+                pr.changed_files,
+                pr.html_url
+            );
+
+            PromptPart {
+                priority: Priority::Description,
+                label: format!(
+                    "{}/{}#{}: {}",
+                    pr.repo_owner, pr.repo_name, pr.number, pr.title
+                ),
+                content,
+                children: Vec::new(),
+            }
+        })
+        .collect();
+
+    combined_parts.extend(pr_parts);
+
+    Ok(combined_parts)
+}
+
+/// Notes which PR was chosen when the branch has several, and how to pick another
+pub fn pr_selection_part(
+    pull_requests: &[github_api::PullRequest],
+    base: Option<&str>,
+) -> Option<PromptPart> {
+    let (chosen, alternatives) = pull_requests.split_first()?;
+    if alternatives.is_empty() {
+        return None;
+    }
+
+    let reason = match base {
+        Some(base) if chosen.base_ref == base => format!("it targets {}", base),
+        Some(base) => format!(
+            "no pull request targets {}, so the best match was used",
+            base
+        ),
+        None => "open pull requests and the default branch are preferred".to_string(),
+    };
+
+    let mut content = format!(
+        "\nThis branch has {} pull requests. Using PR #{} into {} [{}] because {}.\nOther pull requests for this branch:\n",
+        pull_requests.len(),
+        chosen.number,
+        chosen.base_ref,
+        chosen.display_state(),
+        reason
+    );
+    for pr in alternatives {
+        content.push_str(&format!(
+            "- PR #{}: {} into {} [{}] ({})\n",
+            pr.number,
+            pr.title,
+            pr.base_ref,
+            pr.display_state(),
+            pr.html_url
+        ));
+    }
+    content.push_str("Run /pr-current <base branch> to use another one.\n");
+
+    Some(PromptPart {
+        priority: Priority::Description,
+        label: format!(
+            "Using PR #{} of {} for this branch",
+            chosen.number,
+            pull_requests.len()
+        ),
+        content,
+        children: Vec::new(),
+    })
+}
+
+/// Locations GitHub looks for a single pull request template. Both spellings
+/// name the same file on case-insensitive filesystems.
+const PR_TEMPLATE_PATHS: &[&str] = &[
+    ".github/pull_request_template.md",
+    ".github/PULL_REQUEST_TEMPLATE.md",
+    "pull_request_template.md",
+    "PULL_REQUEST_TEMPLATE.md",
+    "docs/pull_request_template.md",
+    "docs/PULL_REQUEST_TEMPLATE.md",
+];
+
+/// Directory holding several pull request templates
+const PR_TEMPLATE_DIR: &str = ".github/PULL_REQUEST_TEMPLATE";
+
+/// Referenced issues fetched for a PR draft, each one is a request
+const MAX_REFERENCED_ISSUES: usize = 10;
+
+/// Finds `#123` and `owner/repo#123` references, without duplicates
+fn find_issue_references(text: &str, owner: &str, repo: &str) -> Vec<(String, String, u32)> {
+    let mut references: Vec<(String, String, u32)> = Vec::new();
+
+    for word in text.split(|c: char| c.is_whitespace() || "(),;:[]".contains(c)) {
+        let Some((prefix, number)) = word.split_once('#') else {
+            continue;
+        };
+        let digits: String = number.chars().take_while(|c| c.is_ascii_digit()).collect();
+        let Ok(number) = digits.parse::<u32>() else {
+            continue;
+        };
+        let reference = match prefix.split_once('/') {
+            Some((ref_owner, ref_repo)) if !ref_owner.is_empty() && !ref_repo.is_empty() => {
+                (ref_owner.to_string(), ref_repo.to_string(), number)
+            }
+            _ if prefix.is_empty() => (owner.to_string(), repo.to_string(), number),
+            _ => continue,
+        };
+        if !references.contains(&reference) {
+            references.push(reference);
+        }
+    }
+
+    references
+}
+
+/// Context for writing the description of a pull request that isn't opened
+/// yet, built from the local commits against the merge-base with `base`
+pub fn pr_draft_data(
+    owner: &str,
+    repo: &str,
+    base: &str,
+    worktree: &zed::Worktree,
+    config: &crate::config::Config,
+) -> Result<Vec<PromptPart>, String> {
+    let cwd = worktree.root_path();
+
+    let merge_base = git::get_merge_base(&cwd, base)
+        .map_err(|e| format!("Failed to find merge-base with {}: {}", base, e))?;
+    let range = format!("{}..HEAD", merge_base);
+    let commits =
+        git::log_messages(&cwd, &range).map_err(|e| format!("Failed to read commits: {}", e))?;
+    if commits.is_empty() {
+        return Err(format!("No commits on this branch compared to {}", base));
+    }
+    let branch = git::get_current_branch(&cwd).unwrap_or_else(|_| "HEAD".to_string());
+
+    let content = format!(
+        "\nDraft a pull request description for branch {} into {} in {}/{}.\nThe branch has {} commits since {}.\n",
+        branch,
+        base,
+        owner,
+        repo,
+        commits.len(),
+        short_sha(&merge_base)
+    );
+    let mut combined_parts = vec![PromptPart {
+        priority: Priority::Description,
+        label: format!("PR draft: {} into {}", branch, base),
+        content,
+        children: Vec::new(),
+    }];
+
+    // A single template, or any of the templates in the template directory
+    let mut template_paths: Vec<String> = PR_TEMPLATE_PATHS
+        .iter()
+        .map(|path| path.to_string())
+        .collect();
+    template_paths.extend(git::list_files(&cwd, PR_TEMPLATE_DIR).unwrap_or_default());
+    let mut template_dirs: Vec<&str> = Vec::new();
+    for path in &template_paths {
+        // Only the first single template of a directory is used
+        let dir = path.rsplit_once('/').map_or("", |(dir, _)| dir);
+        let single = PR_TEMPLATE_PATHS.contains(&path.as_str());
+        if single && template_dirs.contains(&dir) {
+            continue;
+        }
+        let Ok(template) = worktree.read_text_file(path) else {
+            continue;
+        };
+        if single {
+            template_dirs.push(dir);
+        }
+        let content = format!(
+            "\nPull request template ({}):\n```markdown\n{}\n```\n",
+            path,
+            template.trim_end()
+        );
+        combined_parts.push(PromptPart {
+            priority: Priority::Description,
+            label: format!("Template: {}", path),
+            content,
+            children: Vec::new(),
+        });
+    }
+
+    let mut content = String::from("\nCommits:\n");
+    for commit in &commits {
+        content.push_str(&format!("\n{}\n", commit));
+    }
+    combined_parts.push(PromptPart {
+        priority: Priority::Description,
+        label: format!("Commits ({})", commits.len()),
+        content,
+        children: Vec::new(),
+    });
+
+    // Issues referenced in commit messages, the first few are fetched
+    let references = find_issue_references(&commits.join("\n"), owner, repo);
+    if references.len() > MAX_REFERENCED_ISSUES {
+        let skipped = references[MAX_REFERENCED_ISSUES..]
+            .iter()
+            .map(|(issue_owner, issue_repo, number)| {
+                format!("{}/{}#{}", issue_owner, issue_repo, number)
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        combined_parts.push(PromptPart {
+            priority: Priority::Description,
+            label: format!(
+                "{} more referenced issues",
+                references.len() - MAX_REFERENCED_ISSUES
+            ),
+            content: format!(
+                "\nOnly the first {} referenced issues were fetched, also referenced: {}\n",
+                MAX_REFERENCED_ISSUES, skipped
+            ),
+            children: Vec::new(),
+        });
+    }
+    for (issue_owner, issue_repo, number) in references.into_iter().take(MAX_REFERENCED_ISSUES) {
+        let content = match github_api::get_github_issue(&issue_owner, &issue_repo, number, config)
+        {
+            Ok(issue) => format!(
+                "\nReferenced issue {}/{}#{}: {} [{}]\n{}\n\n{}\n",
+                issue_owner,
+                issue_repo,
+                issue.number,
+                issue.title,
+                issue.state,
+                issue.html_url,
+                issue
+                    .body
+                    .as_deref()
+                    .map(|body| markdown::normalize_body(
+                        body,
+                        Some((&issue_owner, &issue_repo)),
+                        config
+                    ))
+                    .filter(|body| !body.is_empty())
+                    .as_deref()
+                    .unwrap_or("No description provided.")
+            ),
+            Err(e) => format!(
+                "\nReferenced issue {}/{}#{} could not be fetched: {}\n",
+                issue_owner, issue_repo, number, e
+            ),
+        };
+        combined_parts.push(PromptPart {
+            priority: Priority::Description,
+            label: format!("Issue {}/{}#{}", issue_owner, issue_repo, number),
+            content,
+            children: Vec::new(),
+        });
+    }
+
+    let stat = git::diff_stat(&cwd, &merge_base).map_err(|e| format!("Failed to diff: {}", e))?;
+    let content = format!("\nChanged files:\n```\n{}\n```\n", stat.trim_end());
+    combined_parts.push(PromptPart {
+        priority: Priority::Description,
+        label: "Changed files".to_string(),
+        content,
+        children: Vec::new(),
+    });
+
+    let patch = git::diff_patch(&cwd, &merge_base).map_err(|e| format!("Failed to diff: {}", e))?;
+    let content = format!(
+        "\nChanges:\n```diff\n{}\n```\n",
+        prompt_utils::truncate_lines(patch.trim_end(), config.max_patch_lines)
+    );
+    combined_parts.push(PromptPart {
+        priority: Priority::Description,
+        label: "Changes".to_string(),
+        content,
+        children: Vec::new(),
+    });
+
+    Ok(combined_parts)
+}
+
+/// Maximum number of pull requests followed in either direction of a stack
+const MAX_STACK_DEPTH: usize = 10;
+
+/// Pull requests stacked on `head_ref`, depth first with their depth
+fn stacked_children(
+    owner: &str,
+    repo: &str,
+    head_ref: &str,
+    depth: usize,
+    config: &crate::config::Config,
+    children: &mut Vec<(usize, github_api::PullRequest)>,
+) {
+    if depth > MAX_STACK_DEPTH {
+        return;
+    }
+    let prs = github_api::get_github_pull_requests_by_base(owner, repo, head_ref, config)
+        .unwrap_or_default();
+    for pr in prs {
+        // Stacks live in one repository, PRs from forks can't be based on them
+        if pr.head_owner.as_deref() != Some(owner)
+            || children.iter().any(|(_, child)| child.number == pr.number)
+        {
+            continue;
+        }
+        let next_head = pr.head_ref.clone();
+        children.push((depth, pr));
+        stacked_children(owner, repo, &next_head, depth + 1, config, children);
+    }
+}
+
+fn stack_entry(pr: &github_api::PullRequest) -> String {
+    format!(
+        "PR #{}: {} [{}] ({} into {})",
+        pr.number,
+        pr.title,
+        pr.display_state(),
+        pr.head_ref,
+        pr.base_ref
+    )
+}
+
+/// Unresolved review threads of a pull request, rendered for an ancestor in a stack
+fn unresolved_threads_text(
+    owner: &str,
+    repo: &str,
+    pr_number: u32,
+    config: &crate::config::Config,
+) -> String {
+    let threads = match github_api::get_github_review_threads(owner, repo, pr_number, config) {
+        Ok(threads) => threads,
+        Err(e) => return format!("Unresolved threads could not be fetched: {}\n", e),
+    };
+
+    let unresolved: Vec<_> = threads.iter().filter(|t| !t.is_resolved).collect();
+    if unresolved.is_empty() {
+        return "No unresolved threads.\n".to_string();
+    }
+
+    let mut text = format!("{} unresolved threads:\n", unresolved.len());
+    for thread in unresolved {
+        let line = thread.line.map(|l| l.to_string()).unwrap_or_default();
+        let location = match thread.line {
+            Some(line) => format!("{}:{}", thread.path, line),
+            None => thread.path.clone(),
+        };
+        let comments: String = thread
+            .comments
+            .iter()
+            .map(|comment| {
+                format!(
+                    "@{}: {}\n",
+                    comment.author,
+                    markdown::normalize_body(&comment.body, Some((owner, repo)), config)
+                )
+            })
+            .collect();
+        text.push_str(&template::render(
+            &config.templates.thread,
+            &[
+                ("location", &location),
+                ("path", &thread.path),
+                ("line", &line),
+                ("comments", &comments),
+            ],
+        ));
+    }
+    text
+}
+
+/// Walks a stack of pull requests, where each PR is based on the head branch of
+/// the previous one, down to the default branch and up through PRs based on
+/// this one. Empty when the pull request isn't part of a stack.
+pub fn pr_stack_data(
+    owner: &str,
+    repo: &str,
+    pull_request: &github_api::PullRequest,
+    config: &crate::config::Config,
+) -> Result<Vec<PromptPart>, String> {
+    // The stack is extra context, so the PR is shown without it rather
+    // than failing the command when the repository can't be fetched
+    let Some(default_branch) = github_api::get_github_repository(owner, repo, config)
+        .ok()
+        .map(|repository| repository.default_branch)
+    else {
+        return Ok(Vec::new());
+    };
+
+    // PRs below this one, nearest first
+    let mut ancestors: Vec<github_api::PullRequest> = Vec::new();
+    let mut base = pull_request.base_ref.clone();
+    while base != default_branch && ancestors.len() < MAX_STACK_DEPTH {
+        let mut prs =
+            github_api::get_github_pull_requests(owner, repo, config, "all", Some((owner, &base)))
+                .unwrap_or_default();
+        // Prefer an open PR when the branch was reused
+        prs.sort_by_key(|pr| pr.display_state() != "open");
+        let Some(pr) = prs.into_iter().next() else {
+            break;
+        };
+        if pr.number == pull_request.number || ancestors.iter().any(|a| a.number == pr.number) {
+            break;
+        }
+        base = pr.base_ref.clone();
+        ancestors.push(pr);
+    }
+
+    let mut children = Vec::new();
+    stacked_children(
+        owner,
+        repo,
+        &pull_request.head_ref,
+        1,
+        config,
+        &mut children,
+    );
+
+    if ancestors.is_empty() && children.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut entries = vec![if base == default_branch {
+        format!("{} (default branch)", base)
+    } else {
+        base.clone()
+    }];
+    entries.extend(ancestors.iter().rev().map(stack_entry));
+    entries.push(format!("{} <- this PR", stack_entry(pull_request)));
+    let mut previous_depth = 0;
+    for (depth, pr) in &children {
+        // Note the parent when the stack branches
+        let entry = if *depth <= previous_depth {
+            format!("{} (based on {})", stack_entry(pr), pr.base_ref)
+        } else {
+            stack_entry(pr)
+        };
+        entries.push(entry);
+        previous_depth = *depth;
+    }
+
+    let mut content = String::from("\nStack of pull requests, from the base branch up:\n");
+    for (i, entry) in entries.iter().enumerate() {
+        content.push_str(&format!("{}. {}\n", i + 1, entry));
+    }
+
+    let mut combined_parts = vec![PromptPart {
+        priority: Priority::Description,
+        label: format!("Stack of {} pull requests", entries.len() - 1),
+        content,
+        children: Vec::new(),
+    }];
+
+    if config.stack_details {
+        for pr in &ancestors {
+            let content = format!(
+                "\nBelow in the stack: PR #{}: {} [{}]\n\n{}\n\n{}",
+                pr.number,
+                pr.title,
+                pr.display_state(),
+                pr.body
+                    .as_deref()
+                    .map(|body| markdown::normalize_body(body, Some((owner, repo)), config))
+                    .filter(|body| !body.is_empty())
+                    .as_deref()
+                    .unwrap_or("No description provided."),
+                unresolved_threads_text(owner, repo, pr.number, config)
+            );
+            combined_parts.push(PromptPart {
+                priority: Priority::Description,
+                label: format!("Stacked below: PR #{}: {}", pr.number, pr.title),
+                content,
+                children: Vec::new(),
+            });
+        }
+    }
+
+    Ok(combined_parts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_issue_references() {
+        let local = |number| ("owner".to_string(), "repo".to_string(), number);
+        let cases = [
+            ("Fix crash (#12)", vec![local(12)]),
+            ("Closes #1, fixes #2; see #1", vec![local(1), local(2)]),
+            (
+                "Port of other/lib#7 and [#8]",
+                vec![("other".to_string(), "lib".to_string(), 7), local(8)],
+            ),
+            ("Fixes:#3\nRefs: #4.", vec![local(3), local(4)]),
+            // Not issue references
+            ("Use C# or item#5, see # 6 and #x", vec![]),
+            ("Bad owner/#9 or /repo#10", vec![]),
+        ];
+
+        for (text, expected) in cases {
+            assert_eq!(
+                find_issue_references(text, "owner", "repo"),
+                expected,
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn renders_review_locations() {
+        let (old, new) = (Side::Old, Side::New);
+        let cases = [
+            (Some((10, 14)), (new, new), false, "a.rs:10-14"),
+            (Some((14, 14)), (new, new), false, "a.rs:14"),
+            (Some((3, 4)), (old, old), false, "a.rs:3-4 (removed lines)"),
+            (
+                Some((20, 14)),
+                (old, new),
+                false,
+                "a.rs: removed line 20 to line 14",
+            ),
+            (
+                Some((9, 12)),
+                (new, old),
+                false,
+                "a.rs: line 9 to removed line 12",
+            ),
+            (
+                Some((7, 7)),
+                (new, new),
+                true,
+                "a.rs:7 (outdated, lines at review time)",
+            ),
+            (None, (new, new), false, "a.rs (whole file)"),
+        ];
+
+        for (range, sides, outdated, expected) in cases {
+            assert_eq!(review_location("a.rs", range, sides, outdated), expected);
+        }
+    }
+}
@@ -1,24 +1,156 @@
 // Configuration for GitHub API
+use std::collections::HashMap;
+
+use crate::markdown::DetailsMode;
+use crate::template::{self, Preset, Templates};
 use crate::zed;
 
 const ENV_GITHUB_TOKEN: &str = "GITHUB_TOKEN";
+const ENV_MAX_PATCH_LINES: &str = "GITHUB_CONTEXT_MAX_PATCH_LINES";
+const ENV_MAX_FILE_LINES: &str = "GITHUB_CONTEXT_MAX_FILE_LINES";
+const ENV_FILE_CONTEXT_LINES: &str = "GITHUB_CONTEXT_FILE_CONTEXT_LINES";
+const ENV_HUNK_CONTEXT_LINES: &str = "GITHUB_CONTEXT_HUNK_CONTEXT_LINES";
+const ENV_STACK_DETAILS: &str = "GITHUB_CONTEXT_STACK_DETAILS";
+const ENV_TOKEN_BUDGET: &str = "GITHUB_CONTEXT_TOKEN_BUDGET";
+const ENV_STRIP_HTML_COMMENTS: &str = "GITHUB_CONTEXT_STRIP_HTML_COMMENTS";
+const ENV_DETAILS: &str = "GITHUB_CONTEXT_DETAILS";
+const ENV_REPLACE_IMAGES: &str = "GITHUB_CONTEXT_REPLACE_IMAGES";
+const ENV_RESOLVE_LINKS: &str = "GITHUB_CONTEXT_RESOLVE_LINKS";
+const ENV_TEMPLATE: &str = "GITHUB_CONTEXT_TEMPLATE";
+const ENV_TEMPLATE_PR: &str = "GITHUB_CONTEXT_TEMPLATE_PR";
+const ENV_TEMPLATE_REVIEW: &str = "GITHUB_CONTEXT_TEMPLATE_REVIEW";
+const ENV_TEMPLATE_COMMENT: &str = "GITHUB_CONTEXT_TEMPLATE_COMMENT";
+const ENV_TEMPLATE_THREAD: &str = "GITHUB_CONTEXT_TEMPLATE_THREAD";
+const ENV_HOST_ALIASES: &str = "GITHUB_CONTEXT_HOST_ALIASES";
+const ENV_REMOTE: &str = "GITHUB_CONTEXT_REMOTE";
+
+const DEFAULT_MAX_PATCH_LINES: usize = 500;
+const DEFAULT_MAX_FILE_LINES: usize = 300;
+const DEFAULT_FILE_CONTEXT_LINES: usize = 5;
+const DEFAULT_HUNK_CONTEXT_LINES: usize = 3;
+const DEFAULT_TOKEN_BUDGET: usize = 30_000;
 
+#[derive(Clone)]
 pub struct Config {
     pub github_token: Option<String>,
+    /// Total number of patch lines rendered before file patches get truncated
+    pub max_patch_lines: usize,
+    /// Number of lines rendered per file before it gets truncated
+    pub max_file_lines: usize,
+    /// Lines of the current file shown above and below each review comment
+    pub file_context_lines: usize,
+    /// Lines of each review comment's diff hunk kept above and below the commented lines
+    pub hunk_context_lines: usize,
+    /// Include descriptions and unresolved threads of the PRs below in a stack
+    pub stack_details: bool,
+    /// Estimated tokens a command may produce before sections get omitted
+    pub token_budget: usize,
+    /// Drop `<!-- -->` comments, e.g. PR template instructions, from bodies
+    pub strip_html_comments: bool,
+    /// How `<details>` blocks in bodies are rendered
+    pub details: DetailsMode,
+    /// Replace images in bodies with their alt text and URL
+    pub replace_images: bool,
+    /// Point relative links in bodies to files in the repository on GitHub
+    pub resolve_links: bool,
+    /// How PRs, reviews, comments and threads are rendered
+    pub templates: Templates,
+    /// Hosts used in remote URLs mapped to the real host, e.g. SSH aliases
+    pub host_aliases: HashMap<String, String>,
+    /// Git remote to use instead of picking one, overridden by `--remote`
+    pub remote: Option<String>,
 }
 
 impl Config {
     pub fn from_worktree(worktree: Option<&zed::Worktree>) -> Self {
-        let github_token = worktree.and_then(|wt| {
-            let env_vars: std::collections::HashMap<String, String> =
-                wt.shell_env().into_iter().collect();
-            env_vars.get(ENV_GITHUB_TOKEN).cloned()
-        });
+        let env_vars: HashMap<String, String> = worktree
+            .map(|wt| wt.shell_env().into_iter().collect())
+            .unwrap_or_default();
 
-        Config { github_token }
+        Config {
+            github_token: env_vars.get(ENV_GITHUB_TOKEN).cloned(),
+            max_patch_lines: parse_env(&env_vars, ENV_MAX_PATCH_LINES)
+                .unwrap_or(DEFAULT_MAX_PATCH_LINES),
+            max_file_lines: parse_env(&env_vars, ENV_MAX_FILE_LINES)
+                .unwrap_or(DEFAULT_MAX_FILE_LINES),
+            file_context_lines: parse_env(&env_vars, ENV_FILE_CONTEXT_LINES)
+                .unwrap_or(DEFAULT_FILE_CONTEXT_LINES),
+            hunk_context_lines: parse_env(&env_vars, ENV_HUNK_CONTEXT_LINES)
+                .unwrap_or(DEFAULT_HUNK_CONTEXT_LINES),
+            stack_details: parse_env(&env_vars, ENV_STACK_DETAILS).unwrap_or(false),
+            token_budget: parse_env(&env_vars, ENV_TOKEN_BUDGET).unwrap_or(DEFAULT_TOKEN_BUDGET),
+            strip_html_comments: parse_env(&env_vars, ENV_STRIP_HTML_COMMENTS).unwrap_or(true),
+            details: parse_env(&env_vars, ENV_DETAILS).unwrap_or(DetailsMode::Unwrap),
+            replace_images: parse_env(&env_vars, ENV_REPLACE_IMAGES).unwrap_or(true),
+            resolve_links: parse_env(&env_vars, ENV_RESOLVE_LINKS).unwrap_or(true),
+            templates: parse_templates(&env_vars),
+            host_aliases: env_vars
+                .get(ENV_HOST_ALIASES)
+                .map(|value| parse_host_aliases(value))
+                .unwrap_or_default(),
+            remote: env_vars
+                .get(ENV_REMOTE)
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty()),
+        }
     }
 
     pub fn default() -> Self {
-        Config { github_token: None }
+        Config {
+            github_token: None,
+            max_patch_lines: DEFAULT_MAX_PATCH_LINES,
+            max_file_lines: DEFAULT_MAX_FILE_LINES,
+            file_context_lines: DEFAULT_FILE_CONTEXT_LINES,
+            hunk_context_lines: DEFAULT_HUNK_CONTEXT_LINES,
+            stack_details: false,
+            token_budget: DEFAULT_TOKEN_BUDGET,
+            strip_html_comments: true,
+            details: DetailsMode::Unwrap,
+            replace_images: true,
+            resolve_links: true,
+            templates: Templates::preset(Preset::Default),
+            host_aliases: HashMap::new(),
+            remote: None,
+        }
     }
 }
+
+fn parse_env<T: std::str::FromStr>(env_vars: &HashMap<String, String>, key: &str) -> Option<T> {
+    env_vars
+        .get(key)
+        .and_then(|value| value.trim().parse().ok())
+}
+
+/// Templates of the configured preset, with any set individually taking precedence
+fn parse_templates(env_vars: &HashMap<String, String>) -> Templates {
+    let mut templates =
+        Templates::preset(parse_env(env_vars, ENV_TEMPLATE).unwrap_or(Preset::Default));
+
+    for (key, value) in [
+        (ENV_TEMPLATE_PR, &mut templates.pull_request),
+        (ENV_TEMPLATE_REVIEW, &mut templates.review),
+        (ENV_TEMPLATE_COMMENT, &mut templates.comment),
+        (ENV_TEMPLATE_THREAD, &mut templates.thread),
+    ] {
+        if let Some(template) = env_vars.get(key).filter(|t| !t.trim().is_empty()) {
+            *value = template::unescape(template);
+        }
+    }
+
+    templates
+}
+
+/// Parses `alias=host` pairs separated by commas
+fn parse_host_aliases(value: &str) -> HashMap<String, String> {
+    value
+        .split(',')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(alias, host)| {
+            (
+                alias.trim().to_ascii_lowercase(),
+                host.trim().to_ascii_lowercase(),
+            )
+        })
+        .filter(|(alias, host)| !alias.is_empty() && !host.is_empty())
+        .collect()
+}
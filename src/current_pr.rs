@@ -0,0 +1,287 @@
+// Finds the pull request belonging to the checked out branch of a worktree
+use crate::config::Config;
+use crate::git;
+use crate::github_api;
+
+pub struct CurrentPullRequest {
+    pub owner: String,
+    pub repo: String,
+    /// Candidates for the branch, best match first
+    pub pull_requests: Vec<github_api::PullRequest>,
+}
+
+impl CurrentPullRequest {
+    pub fn number(&self) -> u32 {
+        self.pull_requests[0].number
+    }
+}
+
+/// The repository pull requests are opened against. This is the configured
+/// remote or `upstream`, and for forks without either the fork's parent.
+fn base_repository(cwd: &str, owner: &str, repo: &str, config: &Config) -> (String, String) {
+    let remote = config.remote.as_deref().unwrap_or("upstream");
+    if let Ok(remote_url) = git::get_remote_repo(cwd, remote, &config.host_aliases) {
+        return (remote_url.owner, remote_url.repo);
+    }
+
+    match github_api::get_github_repository(owner, repo, config) {
+        Ok(github_api::Repository {
+            parent: Some(parent),
+            ..
+        }) => parent,
+        _ => (owner.to_string(), repo.to_string()),
+    }
+}
+
+fn base_rank(pr: &github_api::PullRequest, base: Option<&str>) -> u8 {
+    match base {
+        Some(base) if pr.base_ref != base => 1,
+        _ => 0,
+    }
+}
+
+fn state_rank(pr: &github_api::PullRequest) -> u8 {
+    match pr.display_state() {
+        "open" => 0,
+        "merged" => 1,
+        _ => 2,
+    }
+}
+
+/// Timestamps are ISO 8601, so they sort as strings
+fn last_activity(pr: &github_api::PullRequest) -> &str {
+    pr.merged_at
+        .as_deref()
+        .or(pr.closed_at.as_deref())
+        .unwrap_or(&pr.updated_at)
+}
+
+/// Orders candidate pull requests for a branch, best match first: those
+/// against the requested base, then open PRs, then merged and then closed
+/// ones, then those against the default branch, then the most recent
+fn compare_pull_requests(
+    a: &github_api::PullRequest,
+    b: &github_api::PullRequest,
+    base: Option<&str>,
+    default_branch: Option<&str>,
+) -> std::cmp::Ordering {
+    base_rank(a, base)
+        .cmp(&base_rank(b, base))
+        .then_with(|| state_rank(a).cmp(&state_rank(b)))
+        .then_with(|| base_rank(a, default_branch).cmp(&base_rank(b, default_branch)))
+        .then_with(|| last_activity(b).cmp(last_activity(a)))
+}
+
+fn remote_repo(cwd: &str, remote: &str, config: &Config) -> Result<(String, String), String> {
+    git::get_remote_repo(cwd, remote, &config.host_aliases)
+        .map(|remote_url| (remote_url.owner, remote_url.repo))
+        .map_err(|e| format!("Failed to get repository info: {}", e))
+}
+
+/// Finds the pull requests of the checked out branch. When there are several,
+/// e.g. backports against release branches, those against `base` come first.
+pub fn find_current_pull_request(
+    cwd: &str,
+    config: &Config,
+    base: Option<&str>,
+) -> Result<CurrentPullRequest, String> {
+    // Get the current branch name, git reports "HEAD" when detached
+    let branch = git::get_current_branch(cwd)
+        .ok()
+        .filter(|branch| !branch.is_empty() && branch != "HEAD");
+    let tracking = branch
+        .as_deref()
+        .map(|branch| git::get_tracking_branch(cwd, branch));
+
+    // `gh pr checkout` may track refs/pull/<n>/head, which already names the PR
+    if let Some(tracking) = &tracking {
+        if let Some(number) = tracking.pull_request_number() {
+            let (owner, repo) =
+                remote_repo(cwd, tracking.remote.as_deref().unwrap_or("origin"), config)?;
+            let pull_request =
+                github_api::get_github_pull_request(&owner, &repo, number, config)
+                    .map_err(|e| format!("Failed to get pull request #{}: {}", number, e))?;
+            return Ok(CurrentPullRequest {
+                owner,
+                repo,
+                pull_requests: vec![pull_request],
+            });
+        }
+    }
+
+    // The branch is pushed to the push remote, for forks this is the head repository.
+    // When pushing elsewhere than the upstream, git pushes to a branch of the same name.
+    let (head_remote, head_ref) = match (&tracking, &branch) {
+        (Some(tracking), Some(branch)) => match (&tracking.push_remote, &tracking.remote) {
+            (Some(push_remote), remote) if Some(push_remote) != remote.as_ref() => {
+                (push_remote.clone(), Some(branch.clone()))
+            }
+            (_, Some(remote)) => (
+                remote.clone(),
+                Some(tracking.remote_branch().unwrap_or(branch).to_string()),
+            ),
+            (_, None) => (String::new(), Some(branch.clone())),
+        },
+        _ => (String::new(), None),
+    };
+    // Branches without an upstream are usually pushed to origin
+    let (head_owner, head_repo) = if head_remote.is_empty() {
+        git::get_remote_repo(cwd, "origin", &config.host_aliases)
+            .or_else(|_| git::get_repo(cwd, None, &config.host_aliases))
+            .map(|remote_url| (remote_url.owner, remote_url.repo))
+            .map_err(|e| format!("Failed to get repository info: {}", e))?
+    } else {
+        remote_repo(cwd, &head_remote, config)?
+    };
+    let (owner, repo) = base_repository(cwd, &head_owner, &head_repo, config);
+
+    // Get PRs in any state for this branch, follow-ups often happen right after
+    // merging. Qualified by owner so forks using the same branch name don't match
+    let mut prs = match &head_ref {
+        Some(head_ref) => github_api::get_github_pull_requests(
+            &owner,
+            &repo,
+            config,
+            "all",
+            Some((&head_owner, head_ref)),
+        )
+        .map_err(|e| format!("Failed to get pull requests: {}", e))?,
+        None => Vec::new(),
+    };
+
+    // Detached HEAD, or a branch that isn't pushed under a known name:
+    // look the PR up through the commit instead
+    let head_sha = git::get_head_sha(cwd).ok();
+    if prs.is_empty() {
+        if let Some(sha) = &head_sha {
+            prs = github_api::get_github_commit_pull_requests(&owner, &repo, sha, config)
+                .map_err(|e| format!("Failed to get pull requests for commit: {}", e))?;
+        }
+    }
+
+    if prs.is_empty() {
+        return Err(format!(
+            "No pull requests found in {}/{} for branch {} or commit {}",
+            owner,
+            repo,
+            head_ref.as_deref().unwrap_or("(detached HEAD)"),
+            head_sha.as_deref().unwrap_or("(unknown)")
+        ));
+    }
+
+    // Without a requested base, PRs against the default branch are preferred
+    let default_branch = if prs.len() > 1 && base.is_none() {
+        github_api::get_github_repository(&owner, &repo, config)
+            .map(|repository| repository.default_branch)
+            .ok()
+    } else {
+        None
+    };
+
+    prs.sort_by(|a, b| compare_pull_requests(a, b, base, default_branch.as_deref()));
+
+    Ok(CurrentPullRequest {
+        owner,
+        repo,
+        pull_requests: prs,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A pull request into `base`, `state` being `open`, `merged` or
+    /// `closed`, last active on day `day` of January 2024
+    fn pull_request(number: u32, base: &str, state: &str, day: u32) -> github_api::PullRequest {
+        let timestamp = format!("2024-01-{:02}T12:00:00Z", day);
+        github_api::PullRequest {
+            number,
+            title: format!("PR {}", number),
+            state: if state == "open" { "open" } else { "closed" }.to_string(),
+            html_url: String::new(),
+            body: None,
+            user: github_api::User {
+                login: "octocat".to_string(),
+                id: 1,
+                avatar_url: String::new(),
+                is_bot: false,
+            },
+            created_at: "2024-01-01T00:00:00Z".to_string(),
+            updated_at: timestamp.clone(),
+            head_ref: "feature".to_string(),
+            head_sha: String::new(),
+            head_owner: None,
+            base_ref: base.to_string(),
+            merged_at: (state == "merged").then(|| timestamp.clone()),
+            closed_at: (state != "open").then_some(timestamp),
+            additions: None,
+            deletions: None,
+            changed_files: None,
+        }
+    }
+
+    #[test]
+    fn ranks_pull_requests_of_a_branch() {
+        // (pull requests, requested base, default branch, expected order)
+        let cases = [
+            (
+                vec![
+                    pull_request(1, "main", "closed", 20),
+                    pull_request(2, "main", "merged", 10),
+                    pull_request(3, "main", "open", 5),
+                ],
+                None,
+                None,
+                vec![3, 2, 1],
+            ),
+            // The requested base wins over the state
+            (
+                vec![
+                    pull_request(1, "main", "open", 20),
+                    pull_request(2, "release-1.0", "closed", 10),
+                ],
+                Some("release-1.0"),
+                Some("main"),
+                vec![2, 1],
+            ),
+            // Then the state wins over the default branch
+            (
+                vec![
+                    pull_request(1, "main", "merged", 20),
+                    pull_request(2, "release-1.0", "open", 10),
+                ],
+                None,
+                Some("main"),
+                vec![2, 1],
+            ),
+            (
+                vec![
+                    pull_request(1, "release-1.0", "open", 20),
+                    pull_request(2, "main", "open", 10),
+                ],
+                None,
+                Some("main"),
+                vec![2, 1],
+            ),
+            // The most recent activity breaks ties
+            (
+                vec![
+                    pull_request(1, "main", "merged", 10),
+                    pull_request(2, "main", "merged", 20),
+                    pull_request(3, "main", "closed", 5),
+                    pull_request(4, "main", "closed", 15),
+                ],
+                None,
+                Some("main"),
+                vec![2, 1, 4, 3],
+            ),
+        ];
+
+        for (mut prs, base, default_branch, expected) in cases {
+            prs.sort_by(|a, b| compare_pull_requests(a, b, base, default_branch));
+            let order: Vec<u32> = prs.iter().map(|pr| pr.number).collect();
+            assert_eq!(order, expected, "base {:?}", base);
+        }
+    }
+}
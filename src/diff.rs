@@ -0,0 +1,580 @@
+// Parsing of unified diffs
+
+/// Line ranges from a `@@ -old_start,old_count +new_start,new_count @@` header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HunkHeader {
+    pub old_start: u32,
+    pub old_count: u32,
+    pub new_start: u32,
+    pub new_count: u32,
+}
+
+/// Parses a hunk header, counts default to 1 when left out
+pub fn parse_hunk_header(line: &str) -> Option<HunkHeader> {
+    let ranges = line.strip_prefix("@@ ")?;
+    let (ranges, _) = ranges.split_once(" @@")?;
+    let (old, new) = ranges.split_once(' ')?;
+
+    let parse_range = |range: &str| -> Option<(u32, u32)> {
+        match range.split_once(',') {
+            Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+            None => Some((range.parse().ok()?, 1)),
+        }
+    };
+    let (old_start, old_count) = parse_range(old.strip_prefix('-')?)?;
+    let (new_start, new_count) = parse_range(new.strip_prefix('+')?)?;
+
+    Some(HunkHeader {
+        old_start,
+        old_count,
+        new_start,
+        new_count,
+    })
+}
+
+/// Where a line of the old side of a diff ended up on the new side
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinePosition {
+    Unchanged(u32),
+    /// The line was replaced, by the line at the same place in the new hunk
+    /// or the last line of the hunk when it got shorter
+    Changed(u32),
+    Deleted,
+}
+
+/// Maps old line numbers to new ones using the hunk headers of a diff
+pub struct LineMap {
+    hunks: Vec<HunkHeader>,
+    file_deleted: bool,
+}
+
+impl LineMap {
+    /// Builds the map from the diff of a single file, best produced with `-U0`
+    /// so unchanged context lines don't count as changed
+    pub fn from_diff(diff: &str) -> Self {
+        LineMap {
+            hunks: diff.lines().filter_map(parse_hunk_header).collect(),
+            file_deleted: diff.lines().any(|line| line == "+++ /dev/null"),
+        }
+    }
+
+    pub fn map_line(&self, old_line: u32) -> LinePosition {
+        if self.file_deleted {
+            return LinePosition::Deleted;
+        }
+
+        let mut offset: i64 = 0;
+        for hunk in &self.hunks {
+            // A hunk without old lines inserts after `old_start`
+            let first_changed = if hunk.old_count == 0 {
+                hunk.old_start + 1
+            } else {
+                hunk.old_start
+            };
+            if old_line < first_changed {
+                break;
+            }
+            if old_line < hunk.old_start + hunk.old_count {
+                return if hunk.new_count == 0 {
+                    LinePosition::Deleted
+                } else {
+                    let offset_in_hunk = (old_line - hunk.old_start).min(hunk.new_count - 1);
+                    LinePosition::Changed(hunk.new_start + offset_in_hunk)
+                };
+            }
+            offset += hunk.new_count as i64 - hunk.old_count as i64;
+        }
+
+        LinePosition::Unchanged((old_line as i64 + offset) as u32)
+    }
+}
+
+/// Side of a diff a review comment is on, `LEFT` for removed lines in GitHub's terms
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Old,
+    New,
+}
+
+impl Side {
+    pub fn from_github(side: Option<&str>) -> Self {
+        match side {
+            Some("LEFT") => Side::Old,
+            _ => Side::New,
+        }
+    }
+}
+
+/// Lines of a hunk as they read on one side, with their line numbers and
+/// without the `+`/`-`/` ` prefix
+pub fn side_lines(hunk: &str, side: Side) -> Vec<(u32, &str)> {
+    let mut lines = hunk.lines();
+    let Some(header) = lines.next().and_then(parse_hunk_header) else {
+        return Vec::new();
+    };
+
+    let (mut old_line, mut new_line) = (header.old_start, header.new_start);
+    let mut result = Vec::new();
+    for line in lines {
+        let text = line.get(1..).unwrap_or_default();
+        match line.chars().next() {
+            Some('+') => {
+                if side == Side::New {
+                    result.push((new_line, text));
+                }
+                new_line += 1;
+            }
+            Some('-') => {
+                if side == Side::Old {
+                    result.push((old_line, text));
+                }
+                old_line += 1;
+            }
+            // `\ No newline at end of file`
+            Some('\\') => {}
+            _ => {
+                result.push((
+                    if side == Side::Old {
+                        old_line
+                    } else {
+                        new_line
+                    },
+                    text,
+                ));
+                old_line += 1;
+                new_line += 1;
+            }
+        }
+    }
+
+    result
+}
+
+/// Cuts a hunk down to the lines from `first` to `last` on `side`, plus
+/// `context` lines around them, and rewrites the `@@` header to match.
+/// Returns the hunk unchanged when it doesn't contain those lines.
+pub fn trim_hunk(hunk: &str, side: Side, first: u32, last: u32, context: usize) -> String {
+    let mut lines = hunk.lines();
+    let Some((header_line, header)) = lines
+        .next()
+        .and_then(|line| Some((line, parse_hunk_header(line)?)))
+    else {
+        return hunk.to_string();
+    };
+    let body: Vec<&str> = lines.collect();
+
+    // Old and new line number each body line is at, or would be inserted at
+    let (mut old_line, mut new_line) = (header.old_start, header.new_start);
+    let mut numbers = Vec::with_capacity(body.len());
+    for line in &body {
+        numbers.push((old_line, new_line));
+        match line.chars().next() {
+            Some('+') => new_line += 1,
+            Some('-') => old_line += 1,
+            Some('\\') => {}
+            _ => {
+                old_line += 1;
+                new_line += 1;
+            }
+        }
+    }
+
+    let on_side = |index: usize| {
+        let marker = body[index].chars().next();
+        let (old, new) = numbers[index];
+        match side {
+            Side::Old => {
+                marker != Some('+') && marker != Some('\\') && (first..=last).contains(&old)
+            }
+            Side::New => {
+                marker != Some('-') && marker != Some('\\') && (first..=last).contains(&new)
+            }
+        }
+    };
+    let (Some(first_index), Some(last_index)) = (
+        (0..body.len()).find(|&i| on_side(i)),
+        (0..body.len()).rev().find(|&i| on_side(i)),
+    ) else {
+        return hunk.to_string();
+    };
+
+    let start = first_index.saturating_sub(context);
+    let mut end = (last_index + context + 1).min(body.len());
+    // Keep a `\ No newline at end of file` marker with its line
+    if body.get(end).is_some_and(|line| line.starts_with('\\')) {
+        end += 1;
+    }
+    if start == 0 && end == body.len() {
+        return hunk.to_string();
+    }
+
+    let kept = &body[start..end];
+    let count = |excluded: char| {
+        kept.iter()
+            .filter(|line| !line.starts_with(excluded) && !line.starts_with('\\'))
+            .count() as u32
+    };
+    let (old_count, new_count) = (count('+'), count('-'));
+    let (old_start, new_start) = numbers[start];
+    // A range without lines starts at the line before it
+    let old_start = if old_count == 0 {
+        old_start.saturating_sub(1)
+    } else {
+        old_start
+    };
+    let new_start = if new_count == 0 {
+        new_start.saturating_sub(1)
+    } else {
+        new_start
+    };
+    let section = header_line
+        .strip_prefix("@@ ")
+        .and_then(|rest| rest.split_once(" @@"))
+        .map(|(_, section)| section)
+        .unwrap_or_default();
+
+    let mut trimmed = format!(
+        "@@ -{},{} +{},{} @@{}",
+        old_start, old_count, new_start, new_count, section
+    );
+    for line in kept {
+        trimmed.push('\n');
+        trimmed.push_str(line);
+    }
+    trimmed
+}
+
+/// Contents of the ```` ```suggestion ```` blocks in a review comment body
+pub fn parse_suggestions(body: &str) -> Vec<String> {
+    let mut suggestions = Vec::new();
+    let mut lines = body.lines();
+
+    while let Some(line) = lines.next() {
+        let line = line.trim_start();
+        let fence_length = line.chars().take_while(|c| *c == '`').count();
+        if fence_length < 3 || line[fence_length..].trim() != "suggestion" {
+            continue;
+        }
+
+        let fence = &line[..fence_length];
+        let mut suggestion = Vec::new();
+        for line in lines.by_ref() {
+            if line.trim() == fence {
+                break;
+            }
+            suggestion.push(line.trim_end_matches('\r'));
+        }
+        suggestions.push(suggestion.join("\n"));
+    }
+
+    suggestions
+}
+
+/// Patch replacing `original`, the lines of `path` from `start`, with
+/// `suggestion`. `before` and `after` are context lines around them. Without
+/// lines after the change `git apply` only applies the patch at the end of
+/// the file, unless it's given `--unidiff-zero`.
+pub fn suggestion_patch(
+    path: &str,
+    start: u32,
+    before: &[&str],
+    original: &[&str],
+    suggestion: &str,
+    after: &[&str],
+) -> String {
+    let suggested: Vec<&str> = if suggestion.is_empty() {
+        Vec::new()
+    } else {
+        suggestion.lines().collect()
+    };
+    let old_start = start.saturating_sub(before.len() as u32);
+    let old_count = before.len() + original.len() + after.len();
+    let new_count = before.len() + suggested.len() + after.len();
+    // A range without lines starts at the line before it
+    let new_start = if new_count == 0 {
+        old_start.saturating_sub(1)
+    } else {
+        old_start
+    };
+
+    let mut patch = format!(
+        "--- a/{}\n+++ b/{}\n@@ -{},{} +{},{} @@",
+        path, path, old_start, old_count, new_start, new_count
+    );
+    let lines = (before.iter().map(|line| (' ', line)))
+        .chain(original.iter().map(|line| ('-', line)))
+        .chain(suggested.iter().map(|line| ('+', line)))
+        .chain(after.iter().map(|line| (' ', line)));
+    for (prefix, line) in lines {
+        patch.push('\n');
+        patch.push(prefix);
+        patch.push_str(line);
+    }
+    patch
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_hunk_headers() {
+        // (header, expected old start/count and new start/count)
+        let cases = [
+            ("@@ -10,3 +12,4 @@", (10, 3, 12, 4)),
+            ("@@ -10,3 +12,4 @@ fn main() {", (10, 3, 12, 4)),
+            ("@@ -5 +5 @@", (5, 1, 5, 1)),
+            ("@@ -0,0 +1,20 @@", (0, 0, 1, 20)),
+            ("@@ -1,20 +0,0 @@", (1, 20, 0, 0)),
+            ("@@ -7,0 +8,2 @@", (7, 0, 8, 2)),
+        ];
+
+        for (line, (old_start, old_count, new_start, new_count)) in cases {
+            assert_eq!(
+                parse_hunk_header(line),
+                Some(HunkHeader {
+                    old_start,
+                    old_count,
+                    new_start,
+                    new_count,
+                }),
+                "{}",
+                line
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_hunk_headers() {
+        let cases = [
+            "",
+            "@@",
+            "@@ -1,2 +1,2",
+            "@@ 1,2 1,2 @@",
+            "@@ -a,2 +1,2 @@",
+            "@@ -1,2 +1,b @@",
+            "@@ -1,2 @@",
+            " @@ -1,2 +1,2 @@",
+            "+++ b/src/main.rs",
+        ];
+
+        for line in cases {
+            assert_eq!(parse_hunk_header(line), None, "{}", line);
+        }
+    }
+
+    #[test]
+    fn maps_lines_through_hunks() {
+        // -U0 diff with an insertion after line 2, line 5 replaced by two
+        // lines, and lines 9-10 deleted
+        let diff = "\
+diff --git a/f b/f
+--- a/f
++++ b/f
+@@ -2,0 +3,3 @@
++a
++b
++c
+@@ -5 +8,2 @@
+-e
++E
++E2
+@@ -9,2 +12,0 @@
+-i
+-j
+";
+        let line_map = LineMap::from_diff(diff);
+
+        let cases = [
+            (1, LinePosition::Unchanged(1)),
+            (2, LinePosition::Unchanged(2)),
+            // after the insertion
+            (3, LinePosition::Unchanged(6)),
+            (4, LinePosition::Unchanged(7)),
+            (5, LinePosition::Changed(8)),
+            // offsets of both earlier hunks add up
+            (6, LinePosition::Unchanged(10)),
+            (8, LinePosition::Unchanged(12)),
+            (9, LinePosition::Deleted),
+            (10, LinePosition::Deleted),
+            (11, LinePosition::Unchanged(13)),
+            (100, LinePosition::Unchanged(102)),
+        ];
+
+        for (old_line, expected) in cases {
+            assert_eq!(line_map.map_line(old_line), expected, "line {}", old_line);
+        }
+    }
+
+    #[test]
+    fn maps_changed_lines_within_shrunk_hunks() {
+        // Lines 10-13 replaced by a single line, with 2 lines inserted above
+        let line_map = LineMap::from_diff("@@ -1,0 +2,2 @@\n+x\n+y\n@@ -10,4 +12,1 @@\n");
+
+        assert_eq!(line_map.map_line(10), LinePosition::Changed(12));
+        assert_eq!(line_map.map_line(13), LinePosition::Changed(12));
+        assert_eq!(line_map.map_line(14), LinePosition::Unchanged(13));
+    }
+
+    #[test]
+    fn maps_unchanged_files_to_the_same_line() {
+        let line_map = LineMap::from_diff("");
+        assert_eq!(line_map.map_line(42), LinePosition::Unchanged(42));
+    }
+
+    #[test]
+    fn maps_deleted_files_to_deleted() {
+        let diff = "--- a/f\n+++ /dev/null\n@@ -1,3 +0,0 @@\n-a\n-b\n-c\n";
+        let line_map = LineMap::from_diff(diff);
+        assert_eq!(line_map.map_line(1), LinePosition::Deleted);
+        assert_eq!(line_map.map_line(3), LinePosition::Deleted);
+    }
+
+    /// Checks the header counts match the lines of a hunk
+    fn assert_valid_hunk(hunk: &str) -> HunkHeader {
+        let mut lines = hunk.lines();
+        let header = lines
+            .next()
+            .and_then(parse_hunk_header)
+            .unwrap_or_else(|| panic!("invalid header in\n{}", hunk));
+        let (mut old_count, mut new_count) = (0, 0);
+        for line in lines {
+            match line.chars().next() {
+                Some('+') => new_count += 1,
+                Some('-') => old_count += 1,
+                Some('\\') => {}
+                _ => {
+                    old_count += 1;
+                    new_count += 1;
+                }
+            }
+        }
+        assert_eq!(header.old_count, old_count, "old count of\n{}", hunk);
+        assert_eq!(header.new_count, new_count, "new count of\n{}", hunk);
+        header
+    }
+
+    // Old lines 10-16, new lines 10-17: c (old 12) became C and D (new 12-13)
+    const HUNK: &str = "@@ -10,7 +10,8 @@ fn main() {\n a\n b\n-c\n+C\n+D\n d\n e\n f\n g";
+
+    #[test]
+    fn trims_hunks_to_the_commented_lines() {
+        // (side, first, last, context, expected hunk)
+        let cases = [
+            (
+                Side::New,
+                13,
+                13,
+                1,
+                "@@ -13,1 +12,3 @@ fn main() {\n+C\n+D\n d",
+            ),
+            (Side::Old, 12, 12, 0, "@@ -12,1 +11,0 @@ fn main() {\n-c"),
+            (
+                Side::Old,
+                12,
+                12,
+                1,
+                "@@ -11,2 +11,2 @@ fn main() {\n b\n-c\n+C",
+            ),
+            (
+                Side::New,
+                12,
+                13,
+                0,
+                "@@ -12,0 +12,2 @@ fn main() {\n+C\n+D",
+            ),
+            // at the start of the hunk
+            (
+                Side::New,
+                10,
+                10,
+                1,
+                "@@ -10,2 +10,2 @@ fn main() {\n a\n b",
+            ),
+            (Side::Old, 10, 10, 0, "@@ -10,1 +10,1 @@ fn main() {\n a"),
+            // at the end of the hunk
+            (
+                Side::New,
+                17,
+                17,
+                2,
+                "@@ -14,3 +15,3 @@ fn main() {\n e\n f\n g",
+            ),
+            (Side::Old, 16, 16, 0, "@@ -16,1 +17,1 @@ fn main() {\n g"),
+        ];
+
+        for (side, first, last, context, expected) in cases {
+            let trimmed = trim_hunk(HUNK, side, first, last, context);
+            assert_eq!(trimmed, expected, "{:?} {}-{}", side, first, last);
+            assert_valid_hunk(&trimmed);
+        }
+    }
+
+    #[test]
+    fn keeps_hunks_that_cannot_be_trimmed() {
+        let cases = [
+            // the whole hunk is needed
+            (Side::New, 10, 17, 1),
+            (Side::New, 12, 13, 10),
+            // lines outside of the hunk
+            (Side::New, 99, 99, 1),
+            (Side::Old, 1, 5, 1),
+        ];
+
+        for (side, first, last, context) in cases {
+            assert_eq!(trim_hunk(HUNK, side, first, last, context), HUNK);
+        }
+        assert_eq!(trim_hunk("not a hunk", Side::New, 1, 1, 1), "not a hunk");
+    }
+
+    #[test]
+    fn trims_hunks_of_new_files() {
+        let hunk = "@@ -0,0 +1,4 @@\n+a\n+b\n+c\n+d";
+        let trimmed = trim_hunk(hunk, Side::New, 4, 4, 1);
+        assert_eq!(trimmed, "@@ -0,0 +3,2 @@\n+c\n+d");
+        assert_valid_hunk(&trimmed);
+    }
+
+    #[test]
+    fn keeps_missing_newline_markers() {
+        let hunk = "@@ -1,3 +1,3 @@\n a\n b\n-c\n\\ No newline at end of file\n+C\n\\ No newline at end of file";
+        let trimmed = trim_hunk(hunk, Side::Old, 3, 3, 0);
+        assert_eq!(trimmed, "@@ -3,1 +2,0 @@\n-c\n\\ No newline at end of file");
+        assert_valid_hunk(&trimmed);
+    }
+
+    #[test]
+    fn suggestion_patches_are_valid_hunks() {
+        // (before, original, suggestion, after)
+        let cases = [
+            ("a\nb", "c", "C", "d\ne"),
+            ("a", "b\nc", "B\nC\nD", ""),
+            ("", "a", "", "b"),
+            ("", "c", "C", ""),
+            ("", "c\nd", "", ""),
+        ];
+
+        for (before, original, suggestion, after) in cases {
+            let before: Vec<&str> = before.lines().collect();
+            let original: Vec<&str> = original.lines().collect();
+            let after: Vec<&str> = after.lines().collect();
+            let patch = suggestion_patch("src/main.rs", 12, &before, &original, suggestion, &after);
+            let (files, hunk) = patch.split_at(patch.find("@@").unwrap());
+            assert_eq!(files, "--- a/src/main.rs\n+++ b/src/main.rs\n");
+            let header = assert_valid_hunk(hunk);
+            assert_eq!(header.old_start, 12 - before.len() as u32);
+
+            let old: Vec<&str> = side_lines(hunk, Side::Old)
+                .into_iter()
+                .map(|(_, text)| text)
+                .collect();
+            assert_eq!(old, [&before[..], &original, &after].concat());
+            let new: Vec<&str> = side_lines(hunk, Side::New)
+                .into_iter()
+                .map(|(_, text)| text)
+                .collect();
+            let suggested: Vec<&str> = suggestion.lines().collect();
+            assert_eq!(new, [&before[..], &suggested, &after].concat());
+        }
+    }
+}
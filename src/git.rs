@@ -1,3 +1,7 @@
+use std::collections::HashMap;
+
+use crate::remote_url::RemoteUrl;
+
 pub fn get_current_branch(cwd: &str) -> Result<String, Box<dyn std::error::Error>> {
     let output = zed_extension_api::Command::new("git")
         .arg("-C")
@@ -15,44 +19,333 @@ pub fn get_current_branch(cwd: &str) -> Result<String, Box<dyn std::error::Error
     Ok(branch)
 }
 
-pub fn get_repo(cwd: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+pub fn get_head_sha(cwd: &str) -> Result<String, Box<dyn std::error::Error>> {
     let output = zed_extension_api::Command::new("git")
         .arg("-C")
         .arg(cwd)
-        .arg("config")
-        .arg("--get")
-        .arg("remote.origin.url")
+        .arg("rev-parse")
+        .arg("HEAD")
         .output()?;
 
-    let url = match String::from_utf8(output.stdout) {
+    let sha = match String::from_utf8(output.stdout) {
         Ok(text) => text.trim().to_string(),
-        Err(e) => return Err(format!("Failed to get remote origin URL: {}", e).into()),
+        Err(e) => return Err(format!("Failed to get HEAD commit: {}", e).into()),
+    };
+
+    // An unborn branch has no commit yet
+    if sha.is_empty() {
+        return Err("No commit at HEAD".into());
+    }
+
+    Ok(sha)
+}
+
+/// Resolves a revision to a commit SHA, `None` when it doesn't exist locally
+pub fn resolve_commit(cwd: &str, rev: &str) -> Option<String> {
+    let output = zed_extension_api::Command::new("git")
+        .arg("-C")
+        .arg(cwd)
+        .arg("rev-parse")
+        .arg("--verify")
+        .arg("--quiet")
+        .arg(format!("{}^{{commit}}", rev))
+        .output()
+        .ok()?;
+
+    String::from_utf8(output.stdout)
+        .ok()
+        .map(|text| text.trim().to_string())
+        .filter(|sha| !sha.is_empty())
+}
+
+/// Whether `commit` is an ancestor of (or equal to) `of`, `None` when git
+/// can't tell, e.g. because one of them is missing locally
+pub fn is_ancestor(cwd: &str, commit: &str, of: &str) -> Option<bool> {
+    let output = zed_extension_api::Command::new("git")
+        .arg("-C")
+        .arg(cwd)
+        .arg("merge-base")
+        .arg("--is-ancestor")
+        .arg(commit)
+        .arg(of)
+        .output()
+        .ok()?;
+
+    match output.status {
+        Some(0) => Some(true),
+        Some(1) => Some(false),
+        _ => None,
+    }
+}
+
+/// One line summaries (`<short sha> <subject>`) of the commits in `range`
+pub fn log_oneline(cwd: &str, range: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let output = zed_extension_api::Command::new("git")
+        .arg("-C")
+        .arg(cwd)
+        .arg("log")
+        .arg("--format=%h %s")
+        .arg(range)
+        .output()?;
+
+    let text = match String::from_utf8(output.stdout) {
+        Ok(text) => text,
+        Err(e) => return Err(format!("Failed to read git log: {}", e).into()),
     };
 
-    // Parse the URL to extract owner and repo
-    let parts: Vec<String> = if url.contains("github.com") {
-        if url.starts_with("git@github.com:") {
-            // SSH format: git@github.com:owner/repo.git
-            let path = url
-                .trim_start_matches("git@github.com:")
-                .trim_end_matches(".git");
-            path.split('/').map(String::from).collect()
-        } else if url.starts_with("https://github.com/") {
-            // HTTPS format: https://github.com/owner/repo.git
-            let path = url
-                .trim_start_matches("https://github.com/")
-                .trim_end_matches(".git");
-            path.split('/').map(String::from).collect()
-        } else {
-            return Err("Unsupported GitHub URL format".into());
+    Ok(text.lines().map(String::from).collect())
+}
+
+/// Diff of a file between a commit and the working tree, without context lines
+pub fn diff_against_worktree(
+    cwd: &str,
+    commit: &str,
+    path: &str,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let output = zed_extension_api::Command::new("git")
+        .arg("-C")
+        .arg(cwd)
+        .arg("diff")
+        .arg("-U0")
+        .arg("--no-color")
+        .arg("--no-ext-diff")
+        .arg(commit)
+        .arg("--")
+        .arg(path)
+        .output()?;
+
+    // Fails when the commit is missing locally
+    if output.status != Some(0) {
+        return Err(format!(
+            "Failed to diff {} against {}: {}",
+            path,
+            commit,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+
+    match String::from_utf8(output.stdout) {
+        Ok(text) => Ok(text),
+        Err(e) => Err(format!("Failed to read diff of {}: {}", path, e).into()),
+    }
+}
+
+/// Runs git in `cwd` and returns its stdout, failing on a non-zero exit status
+fn git_output(cwd: &str, args: &[&str]) -> Result<String, Box<dyn std::error::Error>> {
+    let output = zed_extension_api::Command::new("git")
+        .arg("-C")
+        .arg(cwd)
+        .args(args.iter().copied())
+        .output()?;
+
+    if output.status != Some(0) {
+        return Err(format!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+
+    String::from_utf8(output.stdout)
+        .map_err(|e| format!("Failed to read output of git {}: {}", args.join(" "), e).into())
+}
+
+/// The default branch as a remote-tracking ref (e.g. `origin/main`), or a
+/// local branch when no remote knows its default
+pub fn get_default_branch(cwd: &str, remotes: &[&str]) -> Option<String> {
+    for remote in remotes {
+        let head = format!("refs/remotes/{}/HEAD", remote);
+        if let Ok(branch) = git_output(cwd, &["symbolic-ref", "--quiet", "--short", &head]) {
+            return Some(branch.trim().to_string());
         }
+    }
+
+    // `origin/HEAD` is only set when cloning, guess the usual names otherwise
+    remotes
+        .iter()
+        .flat_map(|remote| [format!("{}/main", remote), format!("{}/master", remote)])
+        .chain(["main".to_string(), "master".to_string()])
+        .find(|branch| resolve_commit(cwd, branch).is_some())
+}
+
+pub fn get_merge_base(cwd: &str, base: &str) -> Result<String, Box<dyn std::error::Error>> {
+    Ok(git_output(cwd, &["merge-base", "HEAD", base])?
+        .trim()
+        .to_string())
+}
+
+/// Full commit messages in `range`, oldest first, as `<short sha> <message>`
+pub fn log_messages(cwd: &str, range: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let text = git_output(cwd, &["log", "--reverse", "--format=%x1e%h %B", range])?;
+
+    Ok(text
+        .split('\x1e')
+        .map(|message| message.trim().to_string())
+        .filter(|message| !message.is_empty())
+        .collect())
+}
+
+/// `git diff --stat` of the commits between `base` and HEAD
+pub fn diff_stat(cwd: &str, base: &str) -> Result<String, Box<dyn std::error::Error>> {
+    git_output(cwd, &["diff", "--stat", "--no-color", base, "HEAD"])
+}
+
+/// Patch of the commits between `base` and HEAD
+pub fn diff_patch(cwd: &str, base: &str) -> Result<String, Box<dyn std::error::Error>> {
+    git_output(cwd, &["diff", "--no-color", "--no-ext-diff", base, "HEAD"])
+}
+
+/// Tracked files in a directory of the repository
+pub fn list_files(cwd: &str, path: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    Ok(git_output(cwd, &["ls-files", path])?
+        .lines()
+        .map(String::from)
+        .collect())
+}
+
+/// Reads a single git config value, `None` when it isn't set
+pub fn get_config_value(cwd: &str, key: &str) -> Option<String> {
+    let output = zed_extension_api::Command::new("git")
+        .arg("-C")
+        .arg(cwd)
+        .arg("config")
+        .arg("--get")
+        .arg(key)
+        .output()
+        .ok()?;
+
+    String::from_utf8(output.stdout)
+        .ok()
+        .map(|text| text.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+pub fn get_remote_url(cwd: &str, remote: &str) -> Result<String, Box<dyn std::error::Error>> {
+    get_config_value(cwd, &format!("remote.{}.url", remote))
+        .ok_or_else(|| format!("No remote named {}", remote).into())
+}
+
+/// Upstream configuration of a local branch
+pub struct TrackingBranch {
+    /// Remote name, or a URL when set up by `gh pr checkout` for forks
+    pub remote: Option<String>,
+    pub push_remote: Option<String>,
+    /// Full ref on the remote, e.g. `refs/heads/feature` or `refs/pull/1/head`
+    pub merge: Option<String>,
+}
+
+impl TrackingBranch {
+    /// PR number when the branch tracks a `refs/pull/<n>/head` ref
+    pub fn pull_request_number(&self) -> Option<u32> {
+        self.merge
+            .as_deref()?
+            .strip_prefix("refs/pull/")?
+            .strip_suffix("/head")?
+            .parse()
+            .ok()
+    }
+
+    /// Branch name on the remote the local branch tracks
+    pub fn remote_branch(&self) -> Option<&str> {
+        self.merge.as_deref()?.strip_prefix("refs/heads/")
+    }
+}
+
+pub fn get_tracking_branch(cwd: &str, branch: &str) -> TrackingBranch {
+    TrackingBranch {
+        remote: get_config_value(cwd, &format!("branch.{}.remote", branch)),
+        push_remote: get_config_value(cwd, &format!("branch.{}.pushRemote", branch))
+            .or_else(|| get_config_value(cwd, "remote.pushDefault")),
+        merge: get_config_value(cwd, &format!("branch.{}.merge", branch)),
+    }
+}
+
+pub struct Remote {
+    pub name: String,
+    pub url: String,
+}
+
+/// Lists the remotes of the repository with their fetch URLs
+pub fn list_remotes(cwd: &str) -> Result<Vec<Remote>, Box<dyn std::error::Error>> {
+    let output = zed_extension_api::Command::new("git")
+        .arg("-C")
+        .arg(cwd)
+        .arg("remote")
+        .arg("-v")
+        .output()?;
+
+    let text = match String::from_utf8(output.stdout) {
+        Ok(text) => text,
+        Err(e) => return Err(format!("Failed to list remotes: {}", e).into()),
+    };
+
+    // Lines look like `origin\tgit@github.com:owner/repo.git (fetch)`
+    let remotes = text
+        .lines()
+        .filter(|line| line.ends_with("(fetch)"))
+        .filter_map(|line| {
+            let (name, rest) = line.split_once('\t')?;
+            let url = rest.trim_end_matches("(fetch)").trim();
+            Some(Remote {
+                name: name.to_string(),
+                url: url.to_string(),
+            })
+        })
+        .collect();
+
+    Ok(remotes)
+}
+
+/// Chooses the GitHub repository of the worktree. An explicitly configured
+/// remote wins, then `upstream`, then `origin`, then the first GitHub remote.
+pub fn get_repo(
+    cwd: &str,
+    preferred_remote: Option<&str>,
+    host_aliases: &HashMap<String, String>,
+) -> Result<RemoteUrl, Box<dyn std::error::Error>> {
+    if let Some(remote) = preferred_remote {
+        return get_remote_repo(cwd, remote, host_aliases);
+    }
+
+    let github_remotes: Vec<(String, RemoteUrl)> = list_remotes(cwd)?
+        .into_iter()
+        .filter_map(|remote| {
+            let remote_url = RemoteUrl::parse(&remote.url, host_aliases).ok()?;
+            remote_url.is_github().then_some((remote.name, remote_url))
+        })
+        .collect();
+
+    ["upstream", "origin"]
+        .iter()
+        .find_map(|name| github_remotes.iter().find(|(remote, _)| remote == name))
+        .or_else(|| github_remotes.first())
+        .map(|(_, remote_url)| remote_url.clone())
+        .ok_or_else(|| "No GitHub remote found, only GitHub repositories are supported".into())
+}
+
+/// GitHub repository behind a remote. Accepts a URL as well, as git allows
+/// those in place of remote names.
+pub fn get_remote_repo(
+    cwd: &str,
+    remote: &str,
+    host_aliases: &HashMap<String, String>,
+) -> Result<RemoteUrl, Box<dyn std::error::Error>> {
+    let url = if remote.contains(':') || remote.contains('/') {
+        remote.to_string()
     } else {
-        return Err("Only GitHub repositories are supported".into());
+        get_remote_url(cwd, remote)?
     };
 
-    if parts.len() < 2 {
-        return Err("Could not extract owner and repo from URL".into());
+    let remote_url = RemoteUrl::parse(&url, host_aliases)?;
+    if !remote_url.is_github() {
+        return Err(format!(
+            "Only GitHub repositories are supported, {} points to {}",
+            remote, remote_url.host
+        )
+        .into());
     }
 
-    Ok(parts.into_iter().take(2).collect())
+    Ok(remote_url)
 }
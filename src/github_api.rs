@@ -13,6 +13,17 @@ pub struct PullRequest {
     pub user: User,
     pub created_at: String,
     pub updated_at: String,
+    pub head_ref: String,
+    pub head_sha: String,
+    /// Missing when the head repository (fork) has been deleted
+    pub head_owner: Option<String>,
+    pub base_ref: String,
+    pub merged_at: Option<String>,
+    pub closed_at: Option<String>,
+    /// Only returned when fetching a single pull request
+    pub additions: Option<u32>,
+    pub deletions: Option<u32>,
+    pub changed_files: Option<u32>,
 }
 
 #[derive(Debug, Serialize)]
@@ -20,6 +31,7 @@ pub struct User {
     pub login: String,
     pub id: u32,
     pub avatar_url: String,
+    pub is_bot: bool,
 }
 
 #[derive(Debug, Serialize)]
@@ -33,6 +45,162 @@ pub struct PullRequestComment {
     pub path: String,
     pub diff_hunk: String,
     pub in_reply_to_id: u32,
+    /// Commit the comment currently applies to
+    pub commit_id: String,
+    /// Commit the comment was made on
+    pub original_commit_id: String,
+    /// Line in `commit_id`, missing when the comment is outdated
+    pub line: Option<u32>,
+    /// Line in `original_commit_id`
+    pub original_line: Option<u32>,
+    /// First line of multi-line comments
+    pub start_line: Option<u32>,
+    pub original_start_line: Option<u32>,
+    /// `RIGHT` for added or unchanged lines, `LEFT` for deleted lines
+    pub side: Option<String>,
+    /// Side of `start_line`, multi-line comments can start on deleted lines
+    /// and end on added or unchanged ones
+    pub start_side: Option<String>,
+}
+
+/// Performs an authenticated GET against the GitHub REST API and returns the
+/// decoded JSON body, turning error statuses into errors.
+fn github_get(url: &str, config: &Config) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
+    github_request(zed::http_client::HttpMethod::Get, url, None, config)
+}
+
+/// Largest page size of list endpoints
+const PER_PAGE: usize = 100;
+
+/// GETs every page of a REST endpoint returning a list, until a short page
+fn github_get_pages(
+    url: &str,
+    config: &Config,
+) -> Result<Vec<serde_json::Value>, Box<dyn std::error::Error>> {
+    let separator = if url.contains('?') { '&' } else { '?' };
+    let mut items = Vec::new();
+
+    for page in 1.. {
+        let page_url = format!("{}{}per_page={}&page={}", url, separator, PER_PAGE, page);
+        let serde_json::Value::Array(page_items) = github_get(&page_url, config)? else {
+            return Err("Expected a list".into());
+        };
+        let last_page = page_items.len() < PER_PAGE;
+        items.extend(page_items);
+        if last_page {
+            break;
+        }
+    }
+
+    Ok(items)
+}
+
+/// Runs a GraphQL query and returns its `data` object. GraphQL reports most
+/// errors with a 200 status, so the `errors` list is checked as well.
+fn github_graphql(
+    query: &str,
+    variables: serde_json::Value,
+    config: &Config,
+) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
+    if config.github_token.is_none() {
+        return Err("The GitHub GraphQL API requires GITHUB_TOKEN to be set".into());
+    }
+
+    let body = serde_json::json!({ "query": query, "variables": variables }).to_string();
+    let mut data = github_request(
+        zed::http_client::HttpMethod::Post,
+        "https://api.github.com/graphql",
+        Some(body),
+        config,
+    )?;
+
+    if let Some(errors) = data.get("errors").and_then(|v| v.as_array()) {
+        let messages: Vec<&str> = errors
+            .iter()
+            .filter_map(|error| error.get("message").and_then(|v| v.as_str()))
+            .collect();
+        if !messages.is_empty() {
+            return Err(format!("GitHub GraphQL error: {}", messages.join(", ")).into());
+        }
+    }
+
+    Ok(data["data"].take())
+}
+
+fn github_request(
+    method: zed::http_client::HttpMethod,
+    url: &str,
+    body: Option<String>,
+    config: &Config,
+) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
+    let mut request_builder = zed::http_client::HttpRequest::builder()
+        .method(method)
+        .url(url)
+        .header("Accept", "application/vnd.github.v3+json")
+        .header("User-Agent", "zed-app");
+
+    // Only add Authorization header if token exists
+    if let Some(token) = &config.github_token {
+        request_builder = request_builder.header("Authorization", format!("Bearer {}", token));
+    }
+
+    if let Some(body) = body {
+        request_builder = request_builder
+            .header("Content-Type", "application/json")
+            .body(body);
+    }
+
+    let request = request_builder
+        .build()
+        .map_err(|e| Box::<dyn std::error::Error>::from(e.to_string()))?;
+
+    let response = zed::http_client::fetch(&request)
+        .map_err(|e| Box::<dyn std::error::Error>::from(e.to_string()))?;
+
+    // Check status code from headers
+    let status_code = response
+        .headers
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case("status"))
+        .and_then(|(_, v)| v.split_whitespace().next())
+        .and_then(|s| s.parse::<u16>().ok())
+        .unwrap_or(0);
+
+    let data: serde_json::Value = serde_json::from_slice(&response.body)?;
+
+    // Check for error responses
+    if status_code >= 400 {
+        let error_message = data["message"]
+            .as_str()
+            .unwrap_or("Unknown GitHub API error");
+        return Err(format!("GitHub API error: {} ({})", error_message, status_code).into());
+    }
+
+    Ok(data)
+}
+
+fn parse_user(data: &serde_json::Value) -> Result<User, Box<dyn std::error::Error>> {
+    parse_user_fields(data.get("user").ok_or("Missing user field")?)
+}
+
+fn parse_user_fields(user_data: &serde_json::Value) -> Result<User, Box<dyn std::error::Error>> {
+    Ok(User {
+        login: user_data
+            .get("login")
+            .and_then(|v| v.as_str())
+            .ok_or("Missing user login")?
+            .to_string(),
+        id: user_data
+            .get("id")
+            .and_then(|v| v.as_u64())
+            .ok_or("Missing user id")? as u32,
+        avatar_url: user_data
+            .get("avatar_url")
+            .and_then(|v| v.as_str())
+            .ok_or("Missing avatar_url")?
+            .to_string(),
+        is_bot: user_data.get("type").and_then(|v| v.as_str()) == Some("Bot"),
+    })
 }
 
 fn parse_github_pr_comments(
@@ -41,24 +209,7 @@ fn parse_github_pr_comments(
     let mut comments = Vec::new();
 
     for comment_data in data {
-        let user_data = comment_data.get("user").ok_or("Missing user field")?;
-
-        let user = User {
-            login: user_data
-                .get("login")
-                .and_then(|v| v.as_str())
-                .ok_or("Missing user login")?
-                .to_string(),
-            id: user_data
-                .get("id")
-                .and_then(|v| v.as_u64())
-                .ok_or("Missing user id")? as u32,
-            avatar_url: user_data
-                .get("avatar_url")
-                .and_then(|v| v.as_str())
-                .ok_or("Missing avatar_url")?
-                .to_string(),
-        };
+        let user = parse_user(comment_data)?;
 
         let comment = PullRequestComment {
             id: comment_data
@@ -100,6 +251,40 @@ fn parse_github_pr_comments(
                 .get("in_reply_to_id")
                 .and_then(|v| v.as_u64())
                 .unwrap_or(0) as u32,
+            commit_id: comment_data
+                .get("commit_id")
+                .and_then(|v| v.as_str())
+                .ok_or("Missing comment commit_id")?
+                .to_string(),
+            original_commit_id: comment_data
+                .get("original_commit_id")
+                .and_then(|v| v.as_str())
+                .ok_or("Missing comment original_commit_id")?
+                .to_string(),
+            line: comment_data
+                .get("line")
+                .and_then(|v| v.as_u64())
+                .map(|v| v as u32),
+            original_line: comment_data
+                .get("original_line")
+                .and_then(|v| v.as_u64())
+                .map(|v| v as u32),
+            start_line: comment_data
+                .get("start_line")
+                .and_then(|v| v.as_u64())
+                .map(|v| v as u32),
+            original_start_line: comment_data
+                .get("original_start_line")
+                .and_then(|v| v.as_u64())
+                .map(|v| v as u32),
+            side: comment_data
+                .get("side")
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string()),
+            start_side: comment_data
+                .get("start_side")
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string()),
         };
 
         comments.push(comment);
@@ -114,86 +299,78 @@ pub fn get_github_pr_comments(
     pr_number: u32,
     config: &Config,
 ) -> Result<Vec<PullRequestComment>, Box<dyn std::error::Error>> {
-    // We need to get both issues comments and PR review comments to include outdated ones
-
+    // Review comments include outdated ones, which is what we want for context
     let review_comments_url = format!(
         "https://api.github.com/repos/{}/{}/pulls/{}/comments",
         repo_owner, repo_name, pr_number
     );
 
-    let request_builder = |url: &str| {
-        let mut builder = zed::http_client::HttpRequest::builder()
-            .method(zed::http_client::HttpMethod::Get)
-            .url(url)
-            .header("Accept", "application/vnd.github.v3+json")
-            .header("User-Agent", "zed-app");
-
-        // Only add Authorization header if token exists
-        if let Some(token) = &config.github_token {
-            builder = builder.header("Authorization", format!("Bearer {}", token));
-        }
-
-        builder
-    };
-
-    // Fetch review comments (including outdated ones)
-    let review_request = request_builder(&review_comments_url)
-        .build()
-        .map_err(|e| Box::<dyn std::error::Error>::from(e.to_string()))?;
+    let review_data = github_get_pages(&review_comments_url, config)?;
 
-    let review_response = zed::http_client::fetch(&review_request)
-        .map_err(|e| Box::<dyn std::error::Error>::from(e.to_string()))?;
+    parse_github_pr_comments(&review_data)
+}
 
-    let review_status_code = review_response
-        .headers
-        .iter()
-        .find(|(k, _)| k.eq_ignore_ascii_case("status"))
-        .and_then(|(_, v)| v.split_whitespace().next())
-        .and_then(|s| s.parse::<u16>().ok())
-        .unwrap_or(0);
+#[derive(Debug, Serialize)]
+pub struct Review {
+    pub id: u32,
+    pub user: User,
+    /// APPROVED, CHANGES_REQUESTED, COMMENTED, DISMISSED or PENDING
+    pub state: String,
+    pub body: String,
+    pub submitted_at: Option<String>,
+    pub html_url: String,
+}
 
-    let review_data: Vec<serde_json::Value> = serde_json::from_slice(&review_response.body)?;
+pub fn get_github_pr_reviews(
+    repo_owner: &str,
+    repo_name: &str,
+    pr_number: u32,
+    config: &Config,
+) -> Result<Vec<Review>, Box<dyn std::error::Error>> {
+    let url = format!(
+        "https://api.github.com/repos/{}/{}/pulls/{}/reviews",
+        repo_owner, repo_name, pr_number
+    );
+    let data = github_get_pages(&url, config)?;
 
-    // Check for error responses
-    if review_status_code >= 400 {
-        let error_message = if !review_data.is_empty() && review_data[0].get("message").is_some() {
-            review_data[0]["message"]
-                .as_str()
-                .unwrap_or("Unknown GitHub API error")
-        } else {
-            "Unknown GitHub API error"
-        };
-        return Err(format!(
-            "GitHub API error: {} ({})",
-            error_message, review_status_code
+    data.iter()
+        .map(
+            |review_data| -> Result<Review, Box<dyn std::error::Error>> {
+                Ok(Review {
+                    id: review_data
+                        .get("id")
+                        .and_then(|v| v.as_u64())
+                        .ok_or("Missing review id")? as u32,
+                    user: parse_user(review_data)?,
+                    state: review_data
+                        .get("state")
+                        .and_then(|v| v.as_str())
+                        .ok_or("Missing review state")?
+                        .to_string(),
+                    body: review_data
+                        .get("body")
+                        .and_then(|v| v.as_str())
+                        .unwrap_or_default()
+                        .to_string(),
+                    submitted_at: review_data
+                        .get("submitted_at")
+                        .and_then(|v| v.as_str())
+                        .map(String::from),
+                    html_url: review_data
+                        .get("html_url")
+                        .and_then(|v| v.as_str())
+                        .ok_or("Missing review html_url")?
+                        .to_string(),
+                })
+            },
         )
-        .into());
-    }
-
-    parse_github_pr_comments(&review_data)
+        .collect()
 }
 
 fn parse_github_pull_request(
     data: &serde_json::Value,
 ) -> Result<PullRequest, Box<dyn std::error::Error>> {
-    let user_data = data.get("user").ok_or("Missing user field")?;
-
-    let user = User {
-        login: user_data
-            .get("login")
-            .and_then(|v| v.as_str())
-            .ok_or("Missing user login")?
-            .to_string(),
-        id: user_data
-            .get("id")
-            .and_then(|v| v.as_u64())
-            .ok_or("Missing user id")? as u32,
-        avatar_url: user_data
-            .get("avatar_url")
-            .and_then(|v| v.as_str())
-            .ok_or("Missing avatar_url")?
-            .to_string(),
-    };
+    let user = parse_user(data)?;
 
     let pull_request = PullRequest {
         number: data
@@ -230,6 +407,51 @@ fn parse_github_pull_request(
             .and_then(|v| v.as_str())
             .ok_or("Missing PR updated_at")?
             .to_string(),
+        head_ref: data
+            .get("head")
+            .and_then(|v| v.get("ref"))
+            .and_then(|v| v.as_str())
+            .ok_or("Missing PR head ref")?
+            .to_string(),
+        head_sha: data
+            .get("head")
+            .and_then(|v| v.get("sha"))
+            .and_then(|v| v.as_str())
+            .ok_or("Missing PR head sha")?
+            .to_string(),
+        head_owner: data
+            .get("head")
+            .and_then(|v| v.get("repo"))
+            .and_then(|v| v.get("owner"))
+            .and_then(|v| v.get("login"))
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string()),
+        base_ref: data
+            .get("base")
+            .and_then(|v| v.get("ref"))
+            .and_then(|v| v.as_str())
+            .ok_or("Missing PR base ref")?
+            .to_string(),
+        merged_at: data
+            .get("merged_at")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string()),
+        closed_at: data
+            .get("closed_at")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string()),
+        additions: data
+            .get("additions")
+            .and_then(|v| v.as_u64())
+            .map(|v| v as u32),
+        deletions: data
+            .get("deletions")
+            .and_then(|v| v.as_u64())
+            .map(|v| v as u32),
+        changed_files: data
+            .get("changed_files")
+            .and_then(|v| v.as_u64())
+            .map(|v| v as u32),
     };
 
     Ok(pull_request)
@@ -246,117 +468,52 @@ pub fn get_github_pull_request(
         repo_owner, repo_name, pr_number
     );
 
-    let mut request_builder = zed::http_client::HttpRequest::builder()
-        .method(zed::http_client::HttpMethod::Get)
-        .url(&url)
-        .header("Accept", "application/vnd.github.v3+json")
-        .header("User-Agent", "zed-app");
-
-    // Only add Authorization header if token exists
-    if let Some(token) = &config.github_token {
-        request_builder = request_builder.header("Authorization", format!("Bearer {}", token));
-    }
-
-    let request = request_builder
-        .build()
-        .map_err(|e| Box::<dyn std::error::Error>::from(e.to_string()))?;
-
-    let response = zed::http_client::fetch(&request)
-        .map_err(|e| Box::<dyn std::error::Error>::from(e.to_string()))?;
-
-    // Check status code from headers
-    let status_code = response
-        .headers
-        .iter()
-        .find(|(k, _)| k.eq_ignore_ascii_case("status"))
-        .and_then(|(_, v)| v.split_whitespace().next())
-        .and_then(|s| s.parse::<u16>().ok())
-        .unwrap_or(0);
+    let data = github_get(&url, config)?;
 
-    let data: serde_json::Value = serde_json::from_slice(&response.body)?;
+    parse_github_pull_request(&data)
+}
 
-    // Check for error responses
-    if status_code >= 400 {
-        let error_message = data["message"]
-            .as_str()
-            .unwrap_or("Unknown GitHub API error");
-        return Err(format!("GitHub API error: {} ({})", error_message, status_code).into());
+impl PullRequest {
+    /// `open`, `merged` or `closed`, GitHub reports merged pull requests as closed
+    pub fn display_state(&self) -> &str {
+        if self.merged_at.is_some() {
+            "merged"
+        } else {
+            &self.state
+        }
     }
-
-    parse_github_pull_request(&data)
 }
 
-pub fn get_github_open_pull_requests(
+/// Lists pull requests in the given state (`open`, `closed` or `all`),
+/// optionally only those whose head is `(owner, branch)`. The owner is needed
+/// to tell apart forks using the same branch name.
+pub fn get_github_pull_requests(
     repo_owner: &str,
     repo_name: &str,
     config: &Config,
-    branch: Option<&str>,
+    state: &str,
+    head: Option<(&str, &str)>,
 ) -> Result<Vec<PullRequest>, Box<dyn std::error::Error>> {
-    let url = format!(
-        "https://api.github.com/repos/{}/{}/pulls?state=open",
-        repo_owner, repo_name
+    let mut url = format!(
+        "https://api.github.com/repos/{}/{}/pulls?state={}",
+        repo_owner, repo_name, state
     );
 
-    let mut request_builder = zed::http_client::HttpRequest::builder()
-        .method(zed::http_client::HttpMethod::Get)
-        .url(&url)
-        .header("Accept", "application/vnd.github.v3+json")
-        .header("User-Agent", "zed-app");
-
-    // Only add Authorization header if token exists
-    if let Some(token) = &config.github_token {
-        request_builder = request_builder.header("Authorization", format!("Bearer {}", token));
-    }
-
-    // Add base branch filter if provided
-    if let Some(base_branch) = branch {
-        request_builder = request_builder.header("base", base_branch);
+    // Filter by head if specified
+    if let Some((head_owner, branch)) = head {
+        url.push_str(&format!(
+            "&head={}:{}",
+            encode_query_value(head_owner),
+            encode_query_value(branch)
+        ));
     }
 
-    let request = request_builder
-        .build()
-        .map_err(|e| Box::<dyn std::error::Error>::from(e.to_string()))?;
-
-    let response = zed::http_client::fetch(&request)
-        .map_err(|e| Box::<dyn std::error::Error>::from(e.to_string()))?;
-
-    // Check status code from headers
-    let status_code = response
-        .headers
-        .iter()
-        .find(|(k, _)| k.eq_ignore_ascii_case("status"))
-        .and_then(|(_, v)| v.split_whitespace().next())
-        .and_then(|s| s.parse::<u16>().ok())
-        .unwrap_or(0);
-
-    let data: Vec<serde_json::Value> = serde_json::from_slice(&response.body)?;
-
-    // Check for error responses
-    if status_code >= 400 {
-        let error_message = if !data.is_empty() && data[0].get("message").is_some() {
-            data[0]["message"]
-                .as_str()
-                .unwrap_or("Unknown GitHub API error")
-        } else {
-            "Unknown GitHub API error"
-        };
-        return Err(format!("GitHub API error: {} ({})", error_message, status_code).into());
-    }
+    let data = github_get(&url, config)?;
+    let data = data.as_array().ok_or("Expected a list of pull requests")?;
 
     let mut pull_requests = Vec::new();
     for pr_data in data {
-        // Filter by branch if specified
-        if let Some(branch_name) = branch {
-            if let Some(head) = pr_data.get("head") {
-                if let Some(ref_name) = head.get("ref").and_then(|v| v.as_str()) {
-                    if ref_name != branch_name {
-                        continue; // Skip if branch doesn't match
-                    }
-                }
-            }
-        }
-
-        match parse_github_pull_request(&pr_data) {
+        match parse_github_pull_request(pr_data) {
             Ok(pr) => pull_requests.push(pr),
             Err(e) => {
                 // Log error but continue processing other PRs
@@ -367,3 +524,1041 @@ pub fn get_github_open_pull_requests(
 
     Ok(pull_requests)
 }
+
+/// Percent-encodes a value for use in a query string
+fn encode_query_value(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+#[derive(Debug, Serialize)]
+pub struct Repository {
+    pub owner: String,
+    pub name: String,
+    pub default_branch: String,
+    /// Owner and name of the repository this one was forked from
+    pub parent: Option<(String, String)>,
+}
+
+pub fn get_github_repository(
+    repo_owner: &str,
+    repo_name: &str,
+    config: &Config,
+) -> Result<Repository, Box<dyn std::error::Error>> {
+    let url = format!("https://api.github.com/repos/{}/{}", repo_owner, repo_name);
+
+    let data = github_get(&url, config)?;
+
+    let owner_and_name = |repo: &serde_json::Value| -> Option<(String, String)> {
+        Some((
+            repo.get("owner")?.get("login")?.as_str()?.to_string(),
+            repo.get("name")?.as_str()?.to_string(),
+        ))
+    };
+
+    let (owner, name) = owner_and_name(&data).ok_or("Missing repository owner or name")?;
+
+    Ok(Repository {
+        owner,
+        name,
+        default_branch: data
+            .get("default_branch")
+            .and_then(|v| v.as_str())
+            .ok_or("Missing repository default_branch")?
+            .to_string(),
+        parent: data.get("parent").and_then(owner_and_name),
+    })
+}
+
+#[derive(Debug, Serialize)]
+pub struct Commit {
+    pub sha: String,
+    pub message: String,
+    pub author: String,
+    pub html_url: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChangedFile {
+    pub filename: String,
+    pub status: String,
+    pub additions: u32,
+    pub deletions: u32,
+    pub patch: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Comparison {
+    pub status: String,
+    pub ahead_by: u32,
+    pub behind_by: u32,
+    pub total_commits: u32,
+    pub html_url: String,
+    pub commits: Vec<Commit>,
+    pub files: Vec<ChangedFile>,
+}
+
+fn parse_github_commit(data: &serde_json::Value) -> Result<Commit, Box<dyn std::error::Error>> {
+    let commit_data = data.get("commit").ok_or("Missing commit field")?;
+
+    Ok(Commit {
+        sha: data
+            .get("sha")
+            .and_then(|v| v.as_str())
+            .ok_or("Missing commit sha")?
+            .to_string(),
+        message: commit_data
+            .get("message")
+            .and_then(|v| v.as_str())
+            .ok_or("Missing commit message")?
+            .to_string(),
+        // Prefer the GitHub login, commits from unknown emails only have a name
+        author: data
+            .get("author")
+            .and_then(|v| v.get("login"))
+            .and_then(|v| v.as_str())
+            .or_else(|| {
+                commit_data
+                    .get("author")
+                    .and_then(|v| v.get("name"))
+                    .and_then(|v| v.as_str())
+            })
+            .unwrap_or("unknown")
+            .to_string(),
+        html_url: data
+            .get("html_url")
+            .and_then(|v| v.as_str())
+            .ok_or("Missing commit html_url")?
+            .to_string(),
+    })
+}
+
+fn parse_github_changed_file(
+    data: &serde_json::Value,
+) -> Result<ChangedFile, Box<dyn std::error::Error>> {
+    Ok(ChangedFile {
+        filename: data
+            .get("filename")
+            .and_then(|v| v.as_str())
+            .ok_or("Missing file filename")?
+            .to_string(),
+        status: data
+            .get("status")
+            .and_then(|v| v.as_str())
+            .ok_or("Missing file status")?
+            .to_string(),
+        additions: data.get("additions").and_then(|v| v.as_u64()).unwrap_or(0) as u32,
+        deletions: data.get("deletions").and_then(|v| v.as_u64()).unwrap_or(0) as u32,
+        // Binary and very large files come without a patch
+        patch: data
+            .get("patch")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string()),
+    })
+}
+
+pub fn get_github_comparison(
+    repo_owner: &str,
+    repo_name: &str,
+    base: &str,
+    head: &str,
+    config: &Config,
+) -> Result<Comparison, Box<dyn std::error::Error>> {
+    let url = format!(
+        "https://api.github.com/repos/{}/{}/compare/{}...{}",
+        repo_owner, repo_name, base, head
+    );
+
+    let data = github_get(&url, config)?;
+
+    let commits = data
+        .get("commits")
+        .and_then(|v| v.as_array())
+        .ok_or("Missing comparison commits")?
+        .iter()
+        .map(parse_github_commit)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let files = data
+        .get("files")
+        .and_then(|v| v.as_array())
+        .map(|files| files.iter().map(parse_github_changed_file).collect())
+        .unwrap_or_else(|| Ok(Vec::new()))?;
+
+    Ok(Comparison {
+        status: data
+            .get("status")
+            .and_then(|v| v.as_str())
+            .ok_or("Missing comparison status")?
+            .to_string(),
+        ahead_by: data
+            .get("ahead_by")
+            .and_then(|v| v.as_u64())
+            .ok_or("Missing comparison ahead_by")? as u32,
+        behind_by: data
+            .get("behind_by")
+            .and_then(|v| v.as_u64())
+            .ok_or("Missing comparison behind_by")? as u32,
+        total_commits: data
+            .get("total_commits")
+            .and_then(|v| v.as_u64())
+            .ok_or("Missing comparison total_commits")? as u32,
+        html_url: data
+            .get("html_url")
+            .and_then(|v| v.as_str())
+            .ok_or("Missing comparison html_url")?
+            .to_string(),
+        commits,
+        files,
+    })
+}
+
+#[derive(Debug, Serialize)]
+pub struct Release {
+    pub tag_name: String,
+    pub name: Option<String>,
+    pub body: Option<String>,
+    pub prerelease: bool,
+    pub draft: bool,
+    pub published_at: Option<String>,
+    pub html_url: String,
+}
+
+fn parse_github_release(data: &serde_json::Value) -> Result<Release, Box<dyn std::error::Error>> {
+    Ok(Release {
+        tag_name: data
+            .get("tag_name")
+            .and_then(|v| v.as_str())
+            .ok_or("Missing release tag_name")?
+            .to_string(),
+        name: data
+            .get("name")
+            .and_then(|v| v.as_str())
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_string()),
+        body: data
+            .get("body")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string()),
+        prerelease: data
+            .get("prerelease")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false),
+        draft: data.get("draft").and_then(|v| v.as_bool()).unwrap_or(false),
+        // Drafts are not published yet
+        published_at: data
+            .get("published_at")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string()),
+        html_url: data
+            .get("html_url")
+            .and_then(|v| v.as_str())
+            .ok_or("Missing release html_url")?
+            .to_string(),
+    })
+}
+
+/// Largest page of releases the API returns
+pub const RELEASES_PER_PAGE: usize = 100;
+
+/// Lists a page of releases, newest first. Pages start at 1, and a page
+/// shorter than `RELEASES_PER_PAGE` is the last one.
+pub fn get_github_releases(
+    repo_owner: &str,
+    repo_name: &str,
+    page: u32,
+    config: &Config,
+) -> Result<Vec<Release>, Box<dyn std::error::Error>> {
+    let url = format!(
+        "https://api.github.com/repos/{}/{}/releases?per_page={}&page={}",
+        repo_owner, repo_name, RELEASES_PER_PAGE, page
+    );
+
+    let data = github_get(&url, config)?;
+
+    data.as_array()
+        .ok_or("Expected a list of releases")?
+        .iter()
+        .map(parse_github_release)
+        .collect()
+}
+
+pub fn get_github_release_by_tag(
+    repo_owner: &str,
+    repo_name: &str,
+    tag: &str,
+    config: &Config,
+) -> Result<Release, Box<dyn std::error::Error>> {
+    let url = format!(
+        "https://api.github.com/repos/{}/{}/releases/tags/{}",
+        repo_owner, repo_name, tag
+    );
+
+    let data = github_get(&url, config)?;
+
+    parse_github_release(&data)
+}
+
+#[derive(Debug, Serialize)]
+pub struct DiscussionComment {
+    pub body: String,
+    pub author: String,
+    pub created_at: String,
+    pub html_url: String,
+    pub is_answer: bool,
+    pub replies: Vec<DiscussionComment>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Discussion {
+    pub number: u32,
+    pub title: String,
+    pub body: String,
+    pub html_url: String,
+    pub author: String,
+    pub category: String,
+    pub created_at: String,
+    pub comments: Vec<DiscussionComment>,
+}
+
+const DISCUSSION_QUERY: &str = r#"
+query($owner: String!, $repo: String!, $number: Int!) {
+  repository(owner: $owner, name: $repo) {
+    discussion(number: $number) {
+      number
+      title
+      body
+      url
+      createdAt
+      author { login }
+      category { name }
+      comments(first: 100) {
+        nodes {
+          body
+          url
+          createdAt
+          isAnswer
+          author { login }
+          replies(first: 100) {
+            nodes { body url createdAt isAnswer author { login } }
+          }
+        }
+      }
+    }
+  }
+}
+"#;
+
+/// Deleted accounts come back as a `null` author
+fn parse_graphql_author(data: &serde_json::Value) -> String {
+    data.get("author")
+        .and_then(|v| v.get("login"))
+        .and_then(|v| v.as_str())
+        .unwrap_or("ghost")
+        .to_string()
+}
+
+fn parse_github_discussion_comment(
+    data: &serde_json::Value,
+) -> Result<DiscussionComment, Box<dyn std::error::Error>> {
+    let replies = data
+        .get("replies")
+        .and_then(|v| v.get("nodes"))
+        .and_then(|v| v.as_array())
+        .map(|nodes| nodes.iter().map(parse_github_discussion_comment).collect())
+        .unwrap_or_else(|| Ok(Vec::new()))?;
+
+    Ok(DiscussionComment {
+        body: data
+            .get("body")
+            .and_then(|v| v.as_str())
+            .ok_or("Missing discussion comment body")?
+            .to_string(),
+        author: parse_graphql_author(data),
+        created_at: data
+            .get("createdAt")
+            .and_then(|v| v.as_str())
+            .ok_or("Missing discussion comment createdAt")?
+            .to_string(),
+        html_url: data
+            .get("url")
+            .and_then(|v| v.as_str())
+            .ok_or("Missing discussion comment url")?
+            .to_string(),
+        is_answer: data
+            .get("isAnswer")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false),
+        replies,
+    })
+}
+
+pub fn get_github_discussion(
+    repo_owner: &str,
+    repo_name: &str,
+    number: u32,
+    config: &Config,
+) -> Result<Discussion, Box<dyn std::error::Error>> {
+    let data = github_graphql(
+        DISCUSSION_QUERY,
+        serde_json::json!({ "owner": repo_owner, "repo": repo_name, "number": number }),
+        config,
+    )?;
+
+    let data = data
+        .get("repository")
+        .and_then(|v| v.get("discussion"))
+        .filter(|v| !v.is_null())
+        .ok_or(format!(
+            "Discussion #{} not found in {}/{}",
+            number, repo_owner, repo_name
+        ))?;
+
+    let comments = data
+        .get("comments")
+        .and_then(|v| v.get("nodes"))
+        .and_then(|v| v.as_array())
+        .map(|nodes| nodes.iter().map(parse_github_discussion_comment).collect())
+        .unwrap_or_else(|| Ok(Vec::new()))?;
+
+    Ok(Discussion {
+        number: data
+            .get("number")
+            .and_then(|v| v.as_u64())
+            .ok_or("Missing discussion number")? as u32,
+        title: data
+            .get("title")
+            .and_then(|v| v.as_str())
+            .ok_or("Missing discussion title")?
+            .to_string(),
+        body: data
+            .get("body")
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .to_string(),
+        html_url: data
+            .get("url")
+            .and_then(|v| v.as_str())
+            .ok_or("Missing discussion url")?
+            .to_string(),
+        author: parse_graphql_author(data),
+        category: data
+            .get("category")
+            .and_then(|v| v.get("name"))
+            .and_then(|v| v.as_str())
+            .unwrap_or("Uncategorized")
+            .to_string(),
+        created_at: data
+            .get("createdAt")
+            .and_then(|v| v.as_str())
+            .ok_or("Missing discussion createdAt")?
+            .to_string(),
+        comments,
+    })
+}
+
+#[derive(Debug, Serialize)]
+pub struct GistFile {
+    pub filename: String,
+    pub language: Option<String>,
+    pub content: String,
+    pub truncated: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Gist {
+    pub id: String,
+    pub description: Option<String>,
+    pub html_url: String,
+    pub owner: Option<String>,
+    pub files: Vec<GistFile>,
+    pub comments: u32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GistComment {
+    pub body: String,
+    pub user: User,
+    pub created_at: String,
+}
+
+/// Fetches a gist, as of `revision` when given
+pub fn get_github_gist(
+    gist_id: &str,
+    revision: Option<&str>,
+    config: &Config,
+) -> Result<Gist, Box<dyn std::error::Error>> {
+    let url = match revision {
+        Some(revision) => format!("https://api.github.com/gists/{}/{}", gist_id, revision),
+        None => format!("https://api.github.com/gists/{}", gist_id),
+    };
+
+    let data = github_get(&url, config)?;
+
+    // Files are keyed by filename
+    let files = data
+        .get("files")
+        .and_then(|v| v.as_object())
+        .ok_or("Missing gist files")?
+        .values()
+        .map(|file| -> Result<GistFile, Box<dyn std::error::Error>> {
+            Ok(GistFile {
+                filename: file
+                    .get("filename")
+                    .and_then(|v| v.as_str())
+                    .ok_or("Missing gist file filename")?
+                    .to_string(),
+                language: file
+                    .get("language")
+                    .and_then(|v| v.as_str())
+                    .map(|s| s.to_string()),
+                content: file
+                    .get("content")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or_default()
+                    .to_string(),
+                // GitHub cuts off file contents over ~1MB
+                truncated: file
+                    .get("truncated")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(false),
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Gist {
+        id: data
+            .get("id")
+            .and_then(|v| v.as_str())
+            .ok_or("Missing gist id")?
+            .to_string(),
+        description: data
+            .get("description")
+            .and_then(|v| v.as_str())
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_string()),
+        html_url: data
+            .get("html_url")
+            .and_then(|v| v.as_str())
+            .ok_or("Missing gist html_url")?
+            .to_string(),
+        // Anonymous gists have no owner
+        owner: data
+            .get("owner")
+            .and_then(|v| v.get("login"))
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string()),
+        files,
+        comments: data.get("comments").and_then(|v| v.as_u64()).unwrap_or(0) as u32,
+    })
+}
+
+pub fn get_github_gist_comments(
+    gist_id: &str,
+    config: &Config,
+) -> Result<Vec<GistComment>, Box<dyn std::error::Error>> {
+    let url = format!("https://api.github.com/gists/{}/comments", gist_id);
+
+    let data = github_get(&url, config)?;
+
+    data.as_array()
+        .ok_or("Expected a list of gist comments")?
+        .iter()
+        .map(
+            |comment| -> Result<GistComment, Box<dyn std::error::Error>> {
+                Ok(GistComment {
+                    body: comment
+                        .get("body")
+                        .and_then(|v| v.as_str())
+                        .ok_or("Missing gist comment body")?
+                        .to_string(),
+                    user: parse_user(comment)?,
+                    created_at: comment
+                        .get("created_at")
+                        .and_then(|v| v.as_str())
+                        .ok_or("Missing gist comment created_at")?
+                        .to_string(),
+                })
+            },
+        )
+        .collect()
+}
+
+/// A pull request as returned by the search API
+#[derive(Debug, Serialize)]
+pub struct SearchPullRequest {
+    pub number: u32,
+    pub title: String,
+    pub repo_owner: String,
+    pub repo_name: String,
+    pub html_url: String,
+    pub author: String,
+    pub created_at: String,
+    pub additions: u32,
+    pub deletions: u32,
+    pub changed_files: u32,
+}
+
+/// Returns the user the token belongs to
+pub fn get_github_authenticated_user(config: &Config) -> Result<User, Box<dyn std::error::Error>> {
+    if config.github_token.is_none() {
+        return Err("Finding the authenticated user requires GITHUB_TOKEN to be set".into());
+    }
+
+    let data = github_get("https://api.github.com/user", config)?;
+
+    parse_user_fields(&data)
+}
+
+// The REST search API leaves out the size of pull requests, GraphQL has it
+const REVIEW_REQUESTS_QUERY: &str = r#"
+query {
+  search(query: "review-requested:@me is:open is:pr sort:created-asc", type: ISSUE, first: 50) {
+    nodes {
+      ... on PullRequest {
+        number
+        title
+        url
+        createdAt
+        additions
+        deletions
+        changedFiles
+        author {
+          login
+        }
+        repository {
+          name
+          owner {
+            login
+          }
+        }
+      }
+    }
+  }
+}
+"#;
+
+/// Searches open pull requests where a review is requested from the
+/// authenticated user or one of their teams
+pub fn get_github_review_requests(
+    config: &Config,
+) -> Result<Vec<SearchPullRequest>, Box<dyn std::error::Error>> {
+    let data = github_graphql(REVIEW_REQUESTS_QUERY, serde_json::json!({}), config)?;
+
+    let parse_u32 = |node: &serde_json::Value, field: &str| -> Result<u32, String> {
+        node.get(field)
+            .and_then(|v| v.as_u64())
+            .map(|v| v as u32)
+            .ok_or(format!("Missing search node {}", field))
+    };
+
+    data.get("search")
+        .and_then(|v| v.get("nodes"))
+        .and_then(|v| v.as_array())
+        .ok_or("Missing search nodes")?
+        .iter()
+        .map(
+            |node| -> Result<SearchPullRequest, Box<dyn std::error::Error>> {
+                let repository = node
+                    .get("repository")
+                    .ok_or("Missing search node repository")?;
+
+                Ok(SearchPullRequest {
+                    number: parse_u32(node, "number")?,
+                    title: node
+                        .get("title")
+                        .and_then(|v| v.as_str())
+                        .ok_or("Missing search node title")?
+                        .to_string(),
+                    repo_owner: repository
+                        .get("owner")
+                        .and_then(|v| v.get("login"))
+                        .and_then(|v| v.as_str())
+                        .ok_or("Missing search node repository owner")?
+                        .to_string(),
+                    repo_name: repository
+                        .get("name")
+                        .and_then(|v| v.as_str())
+                        .ok_or("Missing search node repository name")?
+                        .to_string(),
+                    html_url: node
+                        .get("url")
+                        .and_then(|v| v.as_str())
+                        .ok_or("Missing search node url")?
+                        .to_string(),
+                    author: parse_graphql_author(node),
+                    created_at: node
+                        .get("createdAt")
+                        .and_then(|v| v.as_str())
+                        .ok_or("Missing search node createdAt")?
+                        .to_string(),
+                    additions: parse_u32(node, "additions")?,
+                    deletions: parse_u32(node, "deletions")?,
+                    changed_files: parse_u32(node, "changedFiles")?,
+                })
+            },
+        )
+        .collect()
+}
+
+/// Pull requests that contain the given commit, in any state
+pub fn get_github_commit_pull_requests(
+    repo_owner: &str,
+    repo_name: &str,
+    sha: &str,
+    config: &Config,
+) -> Result<Vec<PullRequest>, Box<dyn std::error::Error>> {
+    let url = format!(
+        "https://api.github.com/repos/{}/{}/commits/{}/pulls",
+        repo_owner, repo_name, sha
+    );
+
+    let data = github_get(&url, config)?;
+
+    data.as_array()
+        .ok_or("Expected a list of pull requests")?
+        .iter()
+        .map(parse_github_pull_request)
+        .collect()
+}
+
+#[derive(Debug, Serialize)]
+pub struct Issue {
+    pub number: u32,
+    pub title: String,
+    pub state: String,
+    pub html_url: String,
+    pub body: Option<String>,
+    pub user: User,
+}
+
+/// Fetches an issue, pull requests are returned as issues as well
+pub fn get_github_issue(
+    repo_owner: &str,
+    repo_name: &str,
+    issue_number: u32,
+    config: &Config,
+) -> Result<Issue, Box<dyn std::error::Error>> {
+    let url = format!(
+        "https://api.github.com/repos/{}/{}/issues/{}",
+        repo_owner, repo_name, issue_number
+    );
+
+    let data = github_get(&url, config)?;
+
+    Ok(Issue {
+        number: data
+            .get("number")
+            .and_then(|v| v.as_u64())
+            .ok_or("Missing issue number")? as u32,
+        title: data
+            .get("title")
+            .and_then(|v| v.as_str())
+            .ok_or("Missing issue title")?
+            .to_string(),
+        state: data
+            .get("state")
+            .and_then(|v| v.as_str())
+            .ok_or("Missing issue state")?
+            .to_string(),
+        html_url: data
+            .get("html_url")
+            .and_then(|v| v.as_str())
+            .ok_or("Missing issue html_url")?
+            .to_string(),
+        body: data
+            .get("body")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string()),
+        user: parse_user(&data)?,
+    })
+}
+
+/// Open pull requests targeting `base`, e.g. the next PRs in a stack
+pub fn get_github_pull_requests_by_base(
+    repo_owner: &str,
+    repo_name: &str,
+    base: &str,
+    config: &Config,
+) -> Result<Vec<PullRequest>, Box<dyn std::error::Error>> {
+    let url = format!(
+        "https://api.github.com/repos/{}/{}/pulls?state=open&base={}",
+        repo_owner,
+        repo_name,
+        encode_query_value(base)
+    );
+
+    let data = github_get(&url, config)?;
+
+    data.as_array()
+        .ok_or("Expected a list of pull requests")?
+        .iter()
+        .map(parse_github_pull_request)
+        .collect()
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReviewThreadComment {
+    /// REST id of the comment, matches `PullRequestComment::id`
+    pub id: Option<u32>,
+    pub author: String,
+    pub body: String,
+    pub html_url: String,
+}
+
+/// A review comment with its replies. Only GraphQL knows whether a thread is resolved.
+#[derive(Debug, Serialize)]
+pub struct ReviewThread {
+    pub is_resolved: bool,
+    pub is_outdated: bool,
+    pub path: String,
+    pub line: Option<u32>,
+    pub comments: Vec<ReviewThreadComment>,
+}
+
+const REVIEW_THREADS_QUERY: &str = r#"
+query($owner: String!, $repo: String!, $number: Int!, $cursor: String) {
+  repository(owner: $owner, name: $repo) {
+    pullRequest(number: $number) {
+      reviewThreads(first: 100, after: $cursor) {
+        pageInfo { hasNextPage endCursor }
+        nodes {
+          isResolved
+          isOutdated
+          path
+          line
+          originalLine
+          comments(first: 100) {
+            nodes { databaseId body url author { login } }
+          }
+        }
+      }
+    }
+  }
+}
+"#;
+
+pub fn get_github_review_threads(
+    repo_owner: &str,
+    repo_name: &str,
+    pr_number: u32,
+    config: &Config,
+) -> Result<Vec<ReviewThread>, Box<dyn std::error::Error>> {
+    // Threads come in pages of up to 100
+    let mut threads = Vec::new();
+    let mut cursor: Option<String> = None;
+    loop {
+        let mut data = github_graphql(
+            REVIEW_THREADS_QUERY,
+            serde_json::json!({
+                "owner": repo_owner,
+                "repo": repo_name,
+                "number": pr_number,
+                "cursor": cursor,
+            }),
+            config,
+        )?;
+
+        let review_threads = &mut data["repository"]["pullRequest"]["reviewThreads"];
+        let serde_json::Value::Array(nodes) = review_threads["nodes"].take() else {
+            return Err(format!(
+                "Pull request #{} not found in {}/{}",
+                pr_number, repo_owner, repo_name
+            )
+            .into());
+        };
+        threads.extend(nodes);
+
+        let page_info = &review_threads["pageInfo"];
+        cursor = page_info["endCursor"].as_str().map(String::from);
+        if page_info["hasNextPage"].as_bool() != Some(true) || cursor.is_none() {
+            break;
+        }
+    }
+
+    threads
+        .iter()
+        .map(
+            |thread| -> Result<ReviewThread, Box<dyn std::error::Error>> {
+                let comments = thread
+                    .get("comments")
+                    .and_then(|v| v.get("nodes"))
+                    .and_then(|v| v.as_array())
+                    .ok_or("Missing review thread comments")?
+                    .iter()
+                    .map(
+                        |comment| -> Result<ReviewThreadComment, Box<dyn std::error::Error>> {
+                            Ok(ReviewThreadComment {
+                                id: comment
+                                    .get("databaseId")
+                                    .and_then(|v| v.as_u64())
+                                    .map(|v| v as u32),
+                                author: parse_graphql_author(comment),
+                                body: comment
+                                    .get("body")
+                                    .and_then(|v| v.as_str())
+                                    .ok_or("Missing review thread comment body")?
+                                    .to_string(),
+                                html_url: comment
+                                    .get("url")
+                                    .and_then(|v| v.as_str())
+                                    .ok_or("Missing review thread comment url")?
+                                    .to_string(),
+                            })
+                        },
+                    )
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                Ok(ReviewThread {
+                    is_resolved: thread
+                        .get("isResolved")
+                        .and_then(|v| v.as_bool())
+                        .unwrap_or(false),
+                    is_outdated: thread
+                        .get("isOutdated")
+                        .and_then(|v| v.as_bool())
+                        .unwrap_or(false),
+                    path: thread
+                        .get("path")
+                        .and_then(|v| v.as_str())
+                        .ok_or("Missing review thread path")?
+                        .to_string(),
+                    // Outdated threads only have the line they were made on
+                    line: thread
+                        .get("line")
+                        .and_then(|v| v.as_u64())
+                        .or_else(|| thread.get("originalLine").and_then(|v| v.as_u64()))
+                        .map(|v| v as u32),
+                    comments,
+                })
+            },
+        )
+        .collect()
+}
+
+/// A check run (e.g. GitHub Actions) or commit status on a commit
+#[derive(Debug, Serialize)]
+pub struct Check {
+    pub name: String,
+    /// Conclusion of a check run, or state of a commit status
+    pub result: Option<String>,
+    pub description: Option<String>,
+    pub html_url: Option<String>,
+}
+
+impl Check {
+    pub fn is_failing(&self) -> bool {
+        matches!(
+            self.result.as_deref(),
+            Some(
+                "failure"
+                    | "timed_out"
+                    | "cancelled"
+                    | "action_required"
+                    | "startup_failure"
+                    | "error"
+            )
+        )
+    }
+}
+
+/// Check runs and commit statuses of a commit. Statuses come from older
+/// integrations that don't use the checks API.
+pub fn get_github_checks(
+    repo_owner: &str,
+    repo_name: &str,
+    sha: &str,
+    config: &Config,
+) -> Result<Vec<Check>, Box<dyn std::error::Error>> {
+    // Check runs come in pages of up to 100, busy CI setups have more
+    let mut check_runs = Vec::new();
+    for page in 1.. {
+        let check_runs_url = format!(
+            "https://api.github.com/repos/{}/{}/commits/{}/check-runs?per_page=100&page={}",
+            repo_owner, repo_name, sha, page
+        );
+        let mut check_runs_data = github_get(&check_runs_url, config)?;
+        let total_count = check_runs_data
+            .get("total_count")
+            .and_then(|v| v.as_u64())
+            .unwrap_or_default() as usize;
+        let serde_json::Value::Array(runs) = check_runs_data["check_runs"].take() else {
+            return Err("Expected a list of check runs".into());
+        };
+
+        let last_page = runs.len() < 100;
+        check_runs.extend(runs);
+        if last_page || check_runs.len() >= total_count {
+            break;
+        }
+    }
+
+    let mut checks: Vec<Check> = check_runs
+        .iter()
+        .map(|run| -> Result<Check, Box<dyn std::error::Error>> {
+            Ok(Check {
+                name: run
+                    .get("name")
+                    .and_then(|v| v.as_str())
+                    .ok_or("Missing check run name")?
+                    .to_string(),
+                // Runs without a conclusion haven't finished yet
+                result: run
+                    .get("conclusion")
+                    .and_then(|v| v.as_str())
+                    .or_else(|| run.get("status").and_then(|v| v.as_str()))
+                    .map(String::from),
+                description: run
+                    .get("output")
+                    .and_then(|v| v.get("title"))
+                    .and_then(|v| v.as_str())
+                    .map(String::from),
+                html_url: run
+                    .get("html_url")
+                    .and_then(|v| v.as_str())
+                    .map(String::from),
+            })
+        })
+        .collect::<Result<_, _>>()?;
+
+    let status_url = format!(
+        "https://api.github.com/repos/{}/{}/commits/{}/status",
+        repo_owner, repo_name, sha
+    );
+    let status_data = github_get(&status_url, config)?;
+
+    for status in status_data
+        .get("statuses")
+        .and_then(|v| v.as_array())
+        .ok_or("Expected a list of statuses")?
+    {
+        checks.push(Check {
+            name: status
+                .get("context")
+                .and_then(|v| v.as_str())
+                .ok_or("Missing status context")?
+                .to_string(),
+            result: status
+                .get("state")
+                .and_then(|v| v.as_str())
+                .map(String::from),
+            description: status
+                .get("description")
+                .and_then(|v| v.as_str())
+                .map(String::from),
+            html_url: status
+                .get("target_url")
+                .and_then(|v| v.as_str())
+                .map(String::from),
+        });
+    }
+
+    Ok(checks)
+}
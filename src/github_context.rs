@@ -1,17 +1,42 @@
 mod commands;
 mod config;
+mod current_pr;
+mod diff;
 mod git;
 mod github_api;
+mod markdown;
 mod prompt_utils;
+mod remote_url;
+mod template;
 
 use config::Config;
+use prompt_utils::PromptPart;
 use zed_extension_api as zed;
 
-struct SlashCommandsExampleExtension;
+struct SlashCommandsExampleExtension {
+    /// Root path and config of the last worktree a command ran in. Argument
+    /// completion has no worktree, so this is the only way it can
+    /// authenticate or find the current repository.
+    last_worktree: std::sync::Mutex<Option<(String, Config)>>,
+}
+
+impl SlashCommandsExampleExtension {
+    fn completion_config(&self) -> Config {
+        self.completion_worktree()
+            .map(|(_, config)| config)
+            .unwrap_or_else(Config::default)
+    }
+
+    fn completion_worktree(&self) -> Option<(String, Config)> {
+        self.last_worktree.lock().ok()?.clone()
+    }
+}
 
 impl zed::Extension for SlashCommandsExampleExtension {
     fn new() -> Self {
-        SlashCommandsExampleExtension
+        SlashCommandsExampleExtension {
+            last_worktree: std::sync::Mutex::new(None),
+        }
     }
 
     fn complete_slash_command_argument(
@@ -27,10 +52,11 @@ impl zed::Extension for SlashCommandsExampleExtension {
                 let repo = "zed";
 
                 // Fetch open pull requests
-                match github_api::get_github_open_pull_requests(
+                match github_api::get_github_pull_requests(
                     owner,
                     repo,
                     &Config::default(),
+                    "open",
                     None,
                 ) {
                     Ok(prs) => {
@@ -47,7 +73,65 @@ impl zed::Extension for SlashCommandsExampleExtension {
                     Err(e) => Err(format!("Failed to fetch pull requests: {}", e)),
                 }
             }
-            "pr-current" => Ok(vec![]),
+            "pr-current" => {
+                // Offer the base branches of the PRs for the current branch
+                let Some((cwd, config)) = self.completion_worktree() else {
+                    return Ok(vec![]);
+                };
+                // Branches without a pull request, e.g. the default branch,
+                // just have nothing to complete
+                let Ok(current) = current_pr::find_current_pull_request(&cwd, &config, None) else {
+                    return Ok(vec![]);
+                };
+
+                let mut completions: Vec<zed_extension_api::SlashCommandArgumentCompletion> =
+                    Vec::new();
+                for pr in &current.pull_requests {
+                    if completions.iter().any(|c| c.new_text == pr.base_ref) {
+                        continue;
+                    }
+                    completions.push(zed_extension_api::SlashCommandArgumentCompletion {
+                        label: format!(
+                            "{} (#{}: {} [{}])",
+                            pr.base_ref,
+                            pr.number,
+                            pr.title,
+                            pr.display_state()
+                        ),
+                        new_text: pr.base_ref.clone(),
+                        run_command: true,
+                    });
+                }
+                Ok(completions)
+            }
+            "compare" => Ok(vec![]),
+            "release" => Ok(vec![]),
+            "discussion" => Ok(vec![]),
+            "gist" => Ok(vec![]),
+            "pr-draft" => Ok(vec![]),
+            "my-reviews" => {
+                let config = self.completion_config();
+                match github_api::get_github_review_requests(&config) {
+                    Ok(prs) => {
+                        let completions = prs
+                            .iter()
+                            .map(|pr| zed_extension_api::SlashCommandArgumentCompletion {
+                                label: format!(
+                                    "{}/{}#{}: {}",
+                                    pr.repo_owner, pr.repo_name, pr.number, pr.title
+                                ),
+                                new_text: format!(
+                                    "{}/{}#{}",
+                                    pr.repo_owner, pr.repo_name, pr.number
+                                ),
+                                run_command: true,
+                            })
+                            .collect();
+                        Ok(completions)
+                    }
+                    Err(e) => Err(format!("Failed to fetch review requests: {}", e)),
+                }
+            }
             command => Err(format!("unknown slash command: \"{command}\"")),
         }
     }
@@ -58,7 +142,13 @@ impl zed::Extension for SlashCommandsExampleExtension {
         args: Vec<String>,
         worktree: Option<&zed::Worktree>,
     ) -> Result<zed::SlashCommandOutput, String> {
-        let config = Config::from_worktree(worktree);
+        let mut config = Config::from_worktree(worktree);
+        if let (Some(worktree), Ok(mut cached)) = (worktree, self.last_worktree.lock()) {
+            *cached = Some((worktree.root_path(), config.clone()));
+        }
+        let args = take_remote_arg(args, &mut config)?;
+        let (args, checklist) = take_checklist_arg(args);
+
         match command.name.as_str() {
             "pr-open" => {
                 // Get owner and repo from args if provided
@@ -68,13 +158,10 @@ impl zed::Extension for SlashCommandsExampleExtension {
                     vec![]
                 };
 
-                let owner = parts
-                    .get(0)
-                    .map(|s| *s)
-                    .ok_or("Owner not provided in args")?;
+                let owner = parts.first().copied().ok_or("Owner not provided in args")?;
                 let repo = parts
                     .get(1)
-                    .map(|s| *s)
+                    .copied()
                     .ok_or("Repository not provided in args")?;
 
                 let pr_number = parts
@@ -84,8 +171,10 @@ impl zed::Extension for SlashCommandsExampleExtension {
                     .map_err(|_| "Invalid PR number")?;
 
                 // Use the pr_data function from the commands module to get PR details and comments
-                let pr_prompt_parts = commands::pr_data(owner, repo, pr_number, &config)?;
-                let (text, sections) = prompt_utils::build_slash_command_output(pr_prompt_parts);
+                let pr_prompt_parts =
+                    pull_request_parts(owner, repo, pr_number, &config, None, checklist)?;
+                let (text, sections) =
+                    prompt_utils::build_slash_command_output(pr_prompt_parts, config.token_budget);
 
                 Ok(zed::SlashCommandOutput { sections, text })
             }
@@ -117,10 +206,12 @@ impl zed::Extension for SlashCommandsExampleExtension {
                 let owner = repo_parts[0];
                 let repo = repo_parts[1];
 
-                let pr_prompt_parts = commands::pr_data(owner, repo, pr_number, &config)?;
+                let pr_prompt_parts =
+                    pull_request_parts(owner, repo, pr_number, &config, None, checklist)?;
 
                 // Create sections from parts
-                let (text, sections) = prompt_utils::build_slash_command_output(pr_prompt_parts);
+                let (text, sections) =
+                    prompt_utils::build_slash_command_output(pr_prompt_parts, config.token_budget);
 
                 Ok(zed::SlashCommandOutput { text, sections })
             }
@@ -129,46 +220,212 @@ impl zed::Extension for SlashCommandsExampleExtension {
                     .map(|worktree| worktree.root_path())
                     .unwrap_or_else(|| "No worktree".to_string());
 
-                // Extract owner and repo from git remote URL
-                let (owner, repo) = match crate::git::get_repo(&cwd) {
-                    Ok(parts) if parts.len() >= 2 => (parts[0].clone(), parts[1].clone()),
-                    Ok(_) => {
-                        return Err(
-                            "Could not extract owner and repo from git remote URL".to_string()
-                        )
+                // Optional base branch to choose between several PRs for the branch
+                let base = args.first().map(|base| base.as_str());
+                let current = current_pr::find_current_pull_request(&cwd, &config, base)?;
+
+                let mut pr_prompt_parts = pull_request_parts(
+                    &current.owner,
+                    &current.repo,
+                    current.number(),
+                    &config,
+                    worktree,
+                    checklist,
+                )?;
+                // Stacked PRs follow the description of this PR
+                if !checklist {
+                    let stack_parts = commands::pr_stack_data(
+                        &current.owner,
+                        &current.repo,
+                        &current.pull_requests[0],
+                        &config,
+                    )?;
+                    pr_prompt_parts.splice(1..1, stack_parts);
+                }
+
+                if let Some(selection_part) =
+                    commands::pr_selection_part(&current.pull_requests, base)
+                {
+                    pr_prompt_parts.insert(0, selection_part);
+                }
+
+                // Create sections from parts
+                let (text, sections) =
+                    prompt_utils::build_slash_command_output(pr_prompt_parts, config.token_budget);
+
+                Ok(zed::SlashCommandOutput { text, sections })
+            }
+            "pr-draft" => {
+                let worktree = worktree.ok_or("/pr-draft needs a worktree")?;
+                let cwd = worktree.root_path();
+                let (owner, repo) = repo_from_worktree(Some(worktree), &config)?;
+
+                // Compare against the given branch or the default branch
+                let base = match args.first() {
+                    Some(base) => base.clone(),
+                    None => {
+                        let mut remotes: Vec<&str> =
+                            config.remote.iter().map(|r| r.as_str()).collect();
+                        remotes.extend(["upstream", "origin"]);
+                        git::get_default_branch(&cwd, &remotes).ok_or(
+                            "Could not find the default branch, pass the base branch as argument",
+                        )?
                     }
-                    Err(e) => return Err(format!("Failed to get repository info: {}", e)),
                 };
-                // Get the current branch name
-                let branch = match git::get_current_branch(&cwd) {
-                    Ok(branch) => Some(branch),
-                    Err(_) => None,
+
+                let draft_prompt_parts =
+                    commands::pr_draft_data(&owner, &repo, &base, worktree, &config)?;
+
+                let (text, sections) = prompt_utils::build_slash_command_output(
+                    draft_prompt_parts,
+                    config.token_budget,
+                );
+
+                Ok(zed::SlashCommandOutput { text, sections })
+            }
+            "compare" => {
+                // Either `base...head` or `owner/repo base...head`
+                let (owner, repo, range) = match args.as_slice() {
+                    [range] => {
+                        let (owner, repo) = repo_from_worktree(worktree, &config)?;
+                        (owner, repo, range)
+                    }
+                    [repo_arg, range] => {
+                        let (owner, repo) = parse_owner_repo(repo_arg)?;
+                        (owner, repo, range)
+                    }
+                    _ => return Err("Usage: /compare [owner/repo] base...head".to_string()),
                 };
 
-                // Get open PRs for this repo and branch
-                let prs = github_api::get_github_open_pull_requests(
-                    &owner,
-                    &repo,
-                    &config,
-                    branch.as_deref(),
-                )
-                .map_err(|e| format!("Failed to get pull requests: {}", e))?;
-
-                // Check if there are any PRs
-                if prs.is_empty() {
-                    return Err(format!(
-                        "No open pull requests found for branch: {:?}",
-                        branch
-                    ));
-                }
+                let (base, head) = range
+                    .split_once("...")
+                    .or_else(|| range.split_once(".."))
+                    .ok_or("Range must be given as base...head")?;
 
-                // Use the first PR (most recent)
-                let pr_number = prs[0].number;
+                let compare_prompt_parts =
+                    commands::compare_data(&owner, &repo, base, head, &config)?;
 
-                let pr_prompt_parts = commands::pr_data(&owner, &repo, pr_number, &config)?;
+                let (text, sections) = prompt_utils::build_slash_command_output(
+                    compare_prompt_parts,
+                    config.token_budget,
+                );
 
-                // Create sections from parts
-                let (text, sections) = prompt_utils::build_slash_command_output(pr_prompt_parts);
+                Ok(zed::SlashCommandOutput { text, sections })
+            }
+            "release" => {
+                let target = args
+                    .first()
+                    .ok_or("Usage: /release owner/repo [from..to] or /release <release URL>")?;
+
+                let release_prompt_parts = if target.contains("github.com") {
+                    // https://github.com/owner/repo/releases/tag/v1.2.3
+                    let segments = github_url_segments(target)?;
+                    match segments.as_slice() {
+                        [owner, repo, "releases", "tag", tag @ ..] if !tag.is_empty() => {
+                            commands::single_release_data(owner, repo, &tag.join("/"), &config)?
+                        }
+                        [owner, repo, "releases", ..] => {
+                            commands::release_data(owner, repo, None, None, &config)?
+                        }
+                        _ => return Err("Invalid GitHub release URL format".to_string()),
+                    }
+                } else {
+                    let (owner, repo) = parse_owner_repo(target)?;
+                    let (from, to) = match args.get(1) {
+                        Some(range) => {
+                            let (from, to) = range
+                                .split_once("..")
+                                .ok_or("Range must be given as from..to")?;
+                            (
+                                Some(from).filter(|tag| !tag.is_empty()),
+                                Some(to).filter(|tag| !tag.is_empty()),
+                            )
+                        }
+                        None => (None, None),
+                    };
+                    commands::release_data(&owner, &repo, from, to, &config)?
+                };
+
+                let (text, sections) = prompt_utils::build_slash_command_output(
+                    release_prompt_parts,
+                    config.token_budget,
+                );
+
+                Ok(zed::SlashCommandOutput { text, sections })
+            }
+            "discussion" => {
+                let target = args
+                    .first()
+                    .ok_or("Usage: /discussion <discussion URL or number>")?;
+
+                let (owner, repo, number) = if target.contains("github.com") {
+                    // https://github.com/owner/repo/discussions/123
+                    let segments = github_url_segments(target)?;
+                    match segments.as_slice() {
+                        [owner, repo, "discussions", number, ..] => (
+                            owner.to_string(),
+                            repo.to_string(),
+                            number
+                                .parse::<u32>()
+                                .map_err(|_| "Invalid discussion number in URL")?,
+                        ),
+                        _ => return Err("Invalid GitHub discussion URL format".to_string()),
+                    }
+                } else {
+                    let (owner, repo) = repo_from_worktree(worktree, &config)?;
+                    let number = target
+                        .trim_start_matches('#')
+                        .parse::<u32>()
+                        .map_err(|_| "Invalid discussion number")?;
+                    (owner, repo, number)
+                };
+
+                let discussion_prompt_parts =
+                    commands::discussion_data(&owner, &repo, number, &config)?;
+
+                let (text, sections) = prompt_utils::build_slash_command_output(
+                    discussion_prompt_parts,
+                    config.token_budget,
+                );
+
+                Ok(zed::SlashCommandOutput { text, sections })
+            }
+            "gist" => {
+                let target = args.first().ok_or("Usage: /gist <gist URL or id>")?;
+
+                let (gist_id, revision) = parse_gist_target(target)?;
+
+                let gist_prompt_parts = commands::gist_data(gist_id, revision, &config)?;
+
+                let (text, sections) = prompt_utils::build_slash_command_output(
+                    gist_prompt_parts,
+                    config.token_budget,
+                );
+
+                Ok(zed::SlashCommandOutput { text, sections })
+            }
+            "my-reviews" => {
+                let prompt_parts = match args.first() {
+                    // owner/repo#123, as inserted by argument completion
+                    Some(arg) => {
+                        let (repo_arg, number) = arg
+                            .split_once('#')
+                            .ok_or("Pull request must be given as owner/repo#number")?;
+                        let (owner, repo) = parse_owner_repo(repo_arg)?;
+                        let pr_number = number.parse::<u32>().map_err(|_| "Invalid PR number")?;
+                        pull_request_parts(&owner, &repo, pr_number, &config, None, checklist)?
+                    }
+                    None if checklist => {
+                        return Err(
+                            "--checklist needs a pull request, e.g. /my-reviews --checklist owner/repo#123"
+                                .to_string(),
+                        )
+                    }
+                    None => commands::review_requests_data(&config)?,
+                };
+
+                let (text, sections) =
+                    prompt_utils::build_slash_command_output(prompt_parts, config.token_budget);
 
                 Ok(zed::SlashCommandOutput { text, sections })
             }
@@ -177,4 +434,144 @@ impl zed::Extension for SlashCommandsExampleExtension {
     }
 }
 
+/// Reads owner and repo from the git remote of the worktree
+fn repo_from_worktree(
+    worktree: Option<&zed::Worktree>,
+    config: &Config,
+) -> Result<(String, String), String> {
+    let cwd = worktree
+        .map(|worktree| worktree.root_path())
+        .unwrap_or_else(|| "No worktree".to_string());
+
+    // Extract owner and repo from git remote URL
+    git::get_repo(&cwd, config.remote.as_deref(), &config.host_aliases)
+        .map(|remote_url| (remote_url.owner, remote_url.repo))
+        .map_err(|e| format!("Failed to get repository info: {}", e))
+}
+
+/// Details of a pull request, or its action items with `--checklist`
+fn pull_request_parts(
+    owner: &str,
+    repo: &str,
+    pr_number: u32,
+    config: &Config,
+    worktree: Option<&zed::Worktree>,
+    checklist: bool,
+) -> Result<Vec<PromptPart>, String> {
+    if checklist {
+        commands::pr_checklist_data(owner, repo, pr_number, config)
+    } else {
+        commands::pr_data(owner, repo, pr_number, config, worktree)
+    }
+}
+
+/// Removes `--checklist` from the arguments, returning whether it was given
+fn take_checklist_arg(args: Vec<String>) -> (Vec<String>, bool) {
+    let checklist = args.iter().any(|arg| arg == "--checklist");
+    let args = args
+        .into_iter()
+        .filter(|arg| arg != "--checklist")
+        .collect();
+    (args, checklist)
+}
+
+/// Removes `--remote <name>` from the arguments and applies it to the config
+fn take_remote_arg(args: Vec<String>, config: &mut Config) -> Result<Vec<String>, String> {
+    let mut remaining = Vec::with_capacity(args.len());
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "--remote" {
+            config.remote = Some(args.next().ok_or("--remote requires a remote name")?);
+        } else if let Some(remote) = arg.strip_prefix("--remote=") {
+            config.remote = Some(remote.to_string());
+        } else {
+            remaining.push(arg);
+        }
+    }
+
+    Ok(remaining)
+}
+
+/// Parses an `owner/repo` argument
+fn parse_owner_repo(arg: &str) -> Result<(String, String), String> {
+    match arg.trim_end_matches('/').split_once('/') {
+        Some((owner, repo)) if !owner.is_empty() && !repo.is_empty() && !repo.contains('/') => {
+            Ok((owner.to_string(), repo.to_string()))
+        }
+        _ => Err(format!(
+            "Repository must be given as owner/repo, got: {}",
+            arg
+        )),
+    }
+}
+
+/// Returns the path segments of a github.com URL, e.g. `["owner", "repo", "pull", "1"]`
+fn github_url_segments(url: &str) -> Result<Vec<&str>, String> {
+    let path = url
+        .split_once("github.com/")
+        .map(|(_, path)| path)
+        .ok_or("Not a GitHub URL")?;
+
+    // Drop query strings and anchors
+    let path = path.split(['?', '#']).next().unwrap_or_default();
+
+    Ok(path
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect())
+}
+
+/// Parses a gist URL, `https://gist.github.com/<user>/<id>` optionally followed
+/// by a revision, or a bare id into the gist id and revision
+fn parse_gist_target(target: &str) -> Result<(&str, Option<&str>), String> {
+    let is_revision =
+        |segment: &str| segment.len() == 40 && segment.chars().all(|c| c.is_ascii_hexdigit());
+
+    let segments = if target.contains("github.com/") {
+        github_url_segments(target)?
+    } else {
+        vec![target.trim_end_matches('/')]
+    };
+    match segments[..] {
+        [id] | [_, id] if !id.is_empty() && !id.contains('/') => Ok((id, None)),
+        [_, id, revision] if is_revision(revision) => Ok((id, Some(revision))),
+        _ => Err(format!("Invalid gist URL or id: {}", target)),
+    }
+}
+
 zed::register_extension!(SlashCommandsExampleExtension);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_gist_targets() {
+        let revision = "0123456789abcdef0123456789abcdef01234567";
+        let with_revision = format!("https://gist.github.com/octocat/aa5a315d/{}", revision);
+        let cases = [
+            ("aa5a315d", Some(("aa5a315d", None))),
+            (
+                "https://gist.github.com/octocat/aa5a315d",
+                Some(("aa5a315d", None)),
+            ),
+            (
+                "https://gist.github.com/octocat/aa5a315d/",
+                Some(("aa5a315d", None)),
+            ),
+            (
+                "https://gist.github.com/octocat/aa5a315d#file-a-rs",
+                Some(("aa5a315d", None)),
+            ),
+            ("https://gist.github.com/aa5a315d", Some(("aa5a315d", None))),
+            (&with_revision, Some(("aa5a315d", Some(revision)))),
+            ("https://gist.github.com/octocat/aa5a315d/raw", None),
+            ("https://gist.github.com/", None),
+            ("", None),
+        ];
+
+        for (target, expected) in cases {
+            assert_eq!(parse_gist_target(target).ok(), expected, "{}", target);
+        }
+    }
+}
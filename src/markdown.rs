@@ -0,0 +1,628 @@
+// Cleanup of markdown bodies written on GitHub before they go into context
+use std::str::FromStr;
+
+use crate::Config;
+
+/// How `<details>` blocks in bodies are rendered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DetailsMode {
+    /// Leave the HTML as it is
+    Keep,
+    /// Drop the tags and keep the contents, with the summary in bold
+    Unwrap,
+    /// Replace the whole block with its summary
+    Collapse,
+}
+
+impl FromStr for DetailsMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "keep" => Ok(DetailsMode::Keep),
+            "unwrap" => Ok(DetailsMode::Unwrap),
+            "collapse" => Ok(DetailsMode::Collapse),
+            _ => Err(format!("Unknown details mode: {}", value)),
+        }
+    }
+}
+
+/// Applies the configured cleanups to a PR, issue or comment body. Relative
+/// links are resolved against `repo` (`(owner, name)`) when given. Code
+/// blocks are left alone.
+pub fn normalize_body(body: &str, repo: Option<(&str, &str)>, config: &Config) -> String {
+    // `<details>` blocks usually wrap code blocks, so they are collapsed as a whole
+    let body = match config.details {
+        DetailsMode::Collapse => collapse_details(body),
+        _ => body.to_string(),
+    };
+
+    let mut result = String::new();
+    for (is_code, segment) in split_code_blocks(&body) {
+        if is_code {
+            result.push_str(segment);
+            continue;
+        }
+
+        let mut text = segment.to_string();
+        if config.strip_html_comments {
+            text = strip_html_comments(&text);
+        }
+        if config.details == DetailsMode::Unwrap {
+            text = unwrap_details(&text);
+        }
+        if config.replace_images {
+            text = replace_images(&text);
+        }
+        if let (true, Some(repo)) = (config.resolve_links, repo) {
+            text = resolve_links(&text, repo);
+        }
+        // Removed markup leaves runs of blank lines behind
+        result.push_str(&collapse_blank_lines(&text));
+    }
+
+    result.trim().to_string()
+}
+
+/// Splits text into runs of prose and fenced code blocks, `true` marking code
+fn split_code_blocks(text: &str) -> Vec<(bool, &str)> {
+    let mut segments = Vec::new();
+    let mut segment_start = 0;
+    let mut position = 0;
+    // Fence character and length of the open code block
+    let mut fence: Option<(char, usize)> = None;
+
+    for line in text.split_inclusive('\n') {
+        let trimmed = line.trim();
+        let marker = trimmed.chars().next().filter(|c| *c == '`' || *c == '~');
+        let marker_length = marker
+            .map(|marker| trimmed.chars().take_while(|c| *c == marker).count())
+            .unwrap_or(0);
+
+        match (fence, marker) {
+            (None, Some(marker)) if marker_length >= 3 => {
+                segments.push((false, &text[segment_start..position]));
+                segment_start = position;
+                fence = Some((marker, marker_length));
+            }
+            (Some((open, length)), Some(marker))
+                if marker == open && marker_length >= length && trimmed.len() == marker_length =>
+            {
+                segments.push((true, &text[segment_start..position + line.len()]));
+                segment_start = position + line.len();
+                fence = None;
+            }
+            _ => {}
+        }
+        position += line.len();
+    }
+    // An unclosed fence runs to the end, as GitHub renders it
+    segments.push((fence.is_some(), &text[segment_start..]));
+
+    segments
+        .into_iter()
+        .filter(|(_, segment)| !segment.is_empty())
+        .collect()
+}
+
+fn strip_html_comments(text: &str) -> String {
+    let mut result = String::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find("<!--") {
+        result.push_str(&rest[..start]);
+        // An unclosed comment hides everything after it
+        rest = match rest[start + 4..].find("-->") {
+            Some(end) => &rest[start + 4 + end + 3..],
+            None => "",
+        };
+    }
+    result.push_str(rest);
+
+    result
+}
+
+/// Finds the next HTML tag named `name` (`<name ...>` or `</name>`) at or
+/// after `from`. `lower` is the text lowercased with `to_ascii_lowercase`,
+/// which keeps byte offsets, so tags match in any case. Returns the tag's
+/// byte range and whether it closes.
+fn find_tag(lower: &str, name: &str, from: usize) -> Option<(usize, usize, bool)> {
+    let mut search = from;
+
+    while let Some(offset) = lower[search..].find('<') {
+        let start = search + offset;
+        let after = &lower[start + 1..];
+        let (closing, after) = match after.strip_prefix('/') {
+            Some(after) => (true, after),
+            None => (false, after),
+        };
+        let name_matches = after.strip_prefix(name).is_some_and(|rest| {
+            rest.starts_with(|c: char| c == '>' || c == '/' || c.is_ascii_whitespace())
+        });
+        if name_matches {
+            let end = start + lower[start..].find('>')? + 1;
+            return Some((start, end, closing));
+        }
+        search = start + 1;
+    }
+
+    None
+}
+
+fn unwrap_details(text: &str) -> String {
+    let lower = text.to_ascii_lowercase();
+    let mut result = String::new();
+    let mut position = 0;
+
+    loop {
+        let next = ["details", "summary"]
+            .iter()
+            .filter_map(|name| Some((*name, find_tag(&lower, name, position)?)))
+            .min_by_key(|(_, (start, _, _))| *start);
+        let Some((name, (start, end, closing))) = next else {
+            break;
+        };
+
+        result.push_str(&text[position..start]);
+        match (name, closing) {
+            ("summary", false) => result.push_str("**"),
+            ("summary", true) => result.push_str("**\n"),
+            _ => {}
+        }
+        position = end;
+    }
+    result.push_str(&text[position..]);
+
+    result
+}
+
+fn collapse_details(text: &str) -> String {
+    let lower = text.to_ascii_lowercase();
+    let mut result = String::new();
+    let mut position = 0;
+
+    while let Some((start, open_end, false)) = find_tag(&lower, "details", position) {
+        // Nested blocks are collapsed with their parent
+        let mut depth = 1;
+        let mut search = open_end;
+        let mut block_end = None;
+        while let Some((_, end, closing)) = find_tag(&lower, "details", search) {
+            depth = if closing { depth - 1 } else { depth + 1 };
+            search = end;
+            if depth == 0 {
+                block_end = Some(end);
+                break;
+            }
+        }
+        // An unclosed block is left as it is
+        let Some(block_end) = block_end else {
+            break;
+        };
+
+        let summary = find_tag(&lower, "summary", open_end)
+            .filter(|(summary_start, _, closing)| !closing && *summary_start < block_end)
+            .and_then(|(_, summary_end, _)| {
+                let (close_start, _, _) = find_tag(&lower, "summary", summary_end)?;
+                Some(text[summary_end..close_start].trim())
+            })
+            .filter(|summary| !summary.is_empty());
+
+        result.push_str(&text[position..start]);
+        match summary {
+            Some(summary) => result.push_str(&format!("[Collapsed section: {}]", summary)),
+            None => result.push_str("[Collapsed section]"),
+        }
+        position = block_end;
+    }
+    result.push_str(&text[position..]);
+
+    result
+}
+
+/// Value of an attribute in an HTML tag, e.g. `src` in `<img src="...">`
+fn html_attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let lower = tag.to_ascii_lowercase();
+    let mut search = 0;
+
+    while let Some(offset) = lower[search..].find(name) {
+        let start = search + offset;
+        search = start + name.len();
+        let preceded_by_space = lower[..start].ends_with(|c: char| c.is_ascii_whitespace());
+        let rest = lower[search..].trim_start();
+        if !preceded_by_space || !rest.starts_with('=') {
+            continue;
+        }
+
+        let value_start = tag.len() - rest.len() + 1;
+        let value = tag[value_start..].trim_start();
+        let value_start = tag.len() - value.len();
+        return match value.chars().next()? {
+            quote @ ('"' | '\'') => {
+                let end = value[1..].find(quote)?;
+                Some(&tag[value_start + 1..value_start + 1 + end])
+            }
+            _ => value
+                .split(|c: char| c.is_ascii_whitespace() || c == '>')
+                .next(),
+        };
+    }
+
+    None
+}
+
+/// Replaces markdown and HTML images with a link holding their alt text
+fn replace_images(text: &str) -> String {
+    let mut result = String::new();
+    let mut position = 0;
+
+    // Markdown images, `![alt](url "title")`
+    while let Some(offset) = text[position..].find("![") {
+        let start = position + offset;
+        let Some(image) = parse_link(&text[start + 1..]) else {
+            result.push_str(&text[position..start + 2]);
+            position = start + 2;
+            continue;
+        };
+        result.push_str(&text[position..start]);
+        result.push_str(&image_link(image.text, image.url));
+        position = start + 1 + image.length;
+    }
+    result.push_str(&text[position..]);
+
+    // HTML images, used for screenshots with a set width
+    let text = result;
+    let lower = text.to_ascii_lowercase();
+    let mut result = String::new();
+    let mut position = 0;
+    while let Some((start, end, _)) = find_tag(&lower, "img", position) {
+        let tag = &text[start..end];
+        result.push_str(&text[position..start]);
+        match html_attribute(tag, "src") {
+            Some(src) => result.push_str(&image_link(
+                html_attribute(tag, "alt").unwrap_or_default(),
+                src,
+            )),
+            None => result.push_str(tag),
+        }
+        position = end;
+    }
+    result.push_str(&text[position..]);
+
+    result
+}
+
+fn image_link(alt: &str, url: &str) -> String {
+    if alt.trim().is_empty() {
+        format!("[image]({})", url)
+    } else {
+        format!("[image: {}]({})", alt.trim(), url)
+    }
+}
+
+struct Link<'a> {
+    text: &'a str,
+    url: &'a str,
+    /// Bytes from the opening `[` to the closing `)`
+    length: usize,
+}
+
+/// Parses an inline link, `[text](url "title")`, at the start of `text`.
+/// Links don't span lines.
+fn parse_link(text: &str) -> Option<Link<'_>> {
+    let rest = text.strip_prefix('[')?;
+
+    // Link text may contain balanced brackets, e.g. `[[draft] notes](url)`
+    let mut depth = 0;
+    let mut text_end = None;
+    for (offset, c) in rest.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' if depth == 0 => {
+                text_end = Some(offset);
+                break;
+            }
+            ']' => depth -= 1,
+            '\n' => return None,
+            _ => {}
+        }
+    }
+    let text_end = text_end.filter(|end| rest[end + 1..].starts_with('('))?;
+    let target_start = 1 + text_end + 2;
+
+    // URLs may contain balanced parentheses
+    let mut depth = 0;
+    let mut target_end = None;
+    for (offset, c) in text[target_start..].char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 0 => {
+                target_end = Some(target_start + offset);
+                break;
+            }
+            ')' => depth -= 1,
+            '\n' => return None,
+            _ => {}
+        }
+    }
+    let target_end = target_end?;
+
+    let target = text[target_start..target_end].trim();
+    let url = target.split_whitespace().next().unwrap_or_default();
+    let url = url
+        .strip_prefix('<')
+        .and_then(|url| url.strip_suffix('>'))
+        .unwrap_or(url);
+
+    Some(Link {
+        text: &rest[..text_end],
+        url,
+        length: target_end + 1,
+    })
+}
+
+/// Points links to files in the repository, e.g. `docs/setup.md`, to GitHub
+fn resolve_links(text: &str, (owner, repo): (&str, &str)) -> String {
+    let mut result = String::new();
+    let mut position = 0;
+
+    while let Some(offset) = text[position..].find("](") {
+        let url_start = position + offset + 2;
+        result.push_str(&text[position..url_start]);
+        position = url_start;
+        // Targets end on the line they start on
+        let Some(target_end) = text[url_start..]
+            .find([')', '\n'])
+            .map(|end| url_start + end)
+            .filter(|end| text[*end..].starts_with(')'))
+        else {
+            continue;
+        };
+        let url = text[url_start..target_end]
+            .split_whitespace()
+            .next()
+            .unwrap_or_default();
+
+        if let Some(resolved) = resolve_url(url, owner, repo) {
+            result.push_str(&resolved);
+            position = url_start + url.len();
+        }
+    }
+    result.push_str(&text[position..]);
+
+    result
+}
+
+fn resolve_url(url: &str, owner: &str, repo: &str) -> Option<String> {
+    let has_scheme = url
+        .split_once(':')
+        .is_some_and(|(scheme, _)| !scheme.is_empty() && !scheme.contains('/'));
+    if url.is_empty() || url.starts_with('#') || has_scheme {
+        return None;
+    }
+
+    if let Some(path) = url.strip_prefix("//") {
+        return Some(format!("https://{}", path));
+    }
+    if url.starts_with('/') {
+        return Some(format!("https://github.com{}", url));
+    }
+    // `HEAD` resolves to the default branch
+    Some(format!(
+        "https://github.com/{}/{}/blob/HEAD/{}",
+        owner,
+        repo,
+        url.trim_start_matches("./")
+    ))
+}
+
+fn collapse_blank_lines(text: &str) -> String {
+    let mut result = String::new();
+    let mut blank_lines = 0;
+
+    for line in text.split_inclusive('\n') {
+        if line.trim().is_empty() {
+            blank_lines += 1;
+            if blank_lines > 1 {
+                continue;
+            }
+        } else {
+            blank_lines = 0;
+        }
+        result.push_str(line);
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalize(body: &str, config: &Config) -> String {
+        normalize_body(body, Some(("owner", "repo")), config)
+    }
+
+    #[test]
+    fn leaves_code_blocks_alone() {
+        let cases = [
+            (
+                "Before <!-- hidden -->\n```html\n<!-- kept -->\n<img src=\"a.png\">\n```\nafter",
+                "Before \n```html\n<!-- kept -->\n<img src=\"a.png\">\n```\nafter",
+            ),
+            (
+                "~~~~md\n[docs](docs/setup.md)\n~~~\n~~~~\n[docs](docs/setup.md)",
+                "~~~~md\n[docs](docs/setup.md)\n~~~\n~~~~\n[docs](https://github.com/owner/repo/blob/HEAD/docs/setup.md)",
+            ),
+            // An unclosed fence runs to the end
+            ("```\n<!-- kept -->", "```\n<!-- kept -->"),
+        ];
+
+        for (body, expected) in cases {
+            assert_eq!(normalize(body, &Config::default()), expected, "{}", body);
+        }
+    }
+
+    #[test]
+    fn strips_html_comments() {
+        let cases = [
+            ("a<!-- x -->b", "ab"),
+            ("a <!-- x --> b <!--\ny\n--> c", "a  b  c"),
+            ("<!-- instructions -->\n\n\nDescription", "Description"),
+            // An unclosed comment hides everything after it
+            ("a <!-- x", "a"),
+        ];
+
+        for (body, expected) in cases {
+            assert_eq!(normalize(body, &Config::default()), expected, "{}", body);
+        }
+    }
+
+    #[test]
+    fn renders_details_blocks() {
+        let unwrap = Config::default();
+        let collapse = Config {
+            details: DetailsMode::Collapse,
+            ..Config::default()
+        };
+        let keep = Config {
+            details: DetailsMode::Keep,
+            ..Config::default()
+        };
+
+        let cases = [
+            (
+                &unwrap,
+                "<details><summary>Logs</summary>\nlong\n</details>",
+                "**Logs**\n\nlong",
+            ),
+            (
+                &unwrap,
+                "<DETAILS><SUMMARY>A</SUMMARY>\n<details><summary>B</summary>\nx\n</details></DETAILS>",
+                "**A**\n\n**B**\n\nx",
+            ),
+            (
+                &collapse,
+                "<details><summary>Logs</summary>\n\nlong\n</details>\nafter",
+                "[Collapsed section: Logs]\nafter",
+            ),
+            (
+                &collapse,
+                "<details><summary>Outer</summary>\n<details><summary>Inner</summary>x</details>\n</details>\nafter",
+                "[Collapsed section: Outer]\nafter",
+            ),
+            (
+                &collapse,
+                "<Details open><Summary>Logs</Summary>x</DETAILS>",
+                "[Collapsed section: Logs]",
+            ),
+            (&collapse, "<details>x</details>", "[Collapsed section]"),
+            // An unclosed block is left as it is
+            (
+                &collapse,
+                "<details><summary>Logs</summary>x",
+                "<details><summary>Logs</summary>x",
+            ),
+            (
+                &keep,
+                "<details><summary>Logs</summary>x</details>",
+                "<details><summary>Logs</summary>x</details>",
+            ),
+        ];
+
+        for (config, body, expected) in cases {
+            assert_eq!(normalize(body, config), expected, "{}", body);
+        }
+    }
+
+    #[test]
+    fn replaces_images() {
+        let cases = [
+            (
+                "![screenshot](https://example.com/a.png)",
+                "[image: screenshot](https://example.com/a.png)",
+            ),
+            (
+                "![](https://example.com/a.png)",
+                "[image](https://example.com/a.png)",
+            ),
+            (
+                "![[x] y](<https://example.com/a.png> \"title\")",
+                "[image: [x] y](https://example.com/a.png)",
+            ),
+            (
+                "<img width=\"300\" alt=\"Diagram\" src=\"https://example.com/a.png\">",
+                "[image: Diagram](https://example.com/a.png)",
+            ),
+            (
+                "<IMG SRC='https://example.com/a.png' />",
+                "[image](https://example.com/a.png)",
+            ),
+            ("<img alt=\"no source\">", "<img alt=\"no source\">"),
+            // Brackets that aren't followed by a target aren't link text
+            (
+                "![a] and (b) ![c](https://example.com/c.png)",
+                "![a] and (b) [image: c](https://example.com/c.png)",
+            ),
+            // Unterminated images, or images spanning lines, are left as they are
+            (
+                "![alt](https://example.com/a.png",
+                "![alt](https://example.com/a.png",
+            ),
+            (
+                "![alt\n](https://example.com/a.png)",
+                "![alt\n](https://example.com/a.png)",
+            ),
+            (
+                "![alt](https://example.com/a.png\n)",
+                "![alt](https://example.com/a.png\n)",
+            ),
+        ];
+
+        for (body, expected) in cases {
+            assert_eq!(normalize(body, &Config::default()), expected, "{}", body);
+        }
+    }
+
+    #[test]
+    fn resolves_relative_links() {
+        let cases = [
+            (
+                "[docs](docs/setup.md)",
+                "[docs](https://github.com/owner/repo/blob/HEAD/docs/setup.md)",
+            ),
+            (
+                "[docs](./docs/setup.md \"Setup\")",
+                "[docs](https://github.com/owner/repo/blob/HEAD/docs/setup.md \"Setup\")",
+            ),
+            (
+                "[issues](/owner/repo/issues)",
+                "[issues](https://github.com/owner/repo/issues)",
+            ),
+            (
+                "[cdn](//example.com/a.js)",
+                "[cdn](https://example.com/a.js)",
+            ),
+            (
+                "[site](https://example.com/a)",
+                "[site](https://example.com/a)",
+            ),
+            (
+                "[mail](mailto:a@example.com)",
+                "[mail](mailto:a@example.com)",
+            ),
+            ("[usage](#usage)", "[usage](#usage)"),
+            // Unterminated links are left as they are
+            ("[docs](docs/setup.md", "[docs](docs/setup.md"),
+            ("[docs](docs/setup.md\n)", "[docs](docs/setup.md\n)"),
+        ];
+
+        for (body, expected) in cases {
+            assert_eq!(normalize(body, &Config::default()), expected, "{}", body);
+        }
+        // Without a repository there is nothing to resolve against
+        assert_eq!(
+            normalize_body("[docs](docs/setup.md)", None, &Config::default()),
+            "[docs](docs/setup.md)"
+        );
+    }
+}
@@ -1,33 +1,499 @@
 use crate::zed;
 
+/// Order in which parts get a share of the token budget, highest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    /// The subject of the command, e.g. the PR description, and anything unranked
+    Description,
+    UnresolvedThread,
+    Review,
+    ResolvedThread,
+    Bot,
+}
+
+impl Priority {
+    fn describe(&self) -> &'static str {
+        match self {
+            Priority::Description => "other sections",
+            Priority::UnresolvedThread => "unresolved thread comments",
+            Priority::Review => "reviews",
+            Priority::ResolvedThread => "resolved thread comments",
+            Priority::Bot => "bot comments",
+        }
+    }
+}
+
+/// A section of slash command output. Children are rendered after the
+/// content, inside the part's section, so they can be folded together.
 pub struct PromptPart {
-    pub length: usize,
     pub label: String,
     pub content: String,
+    pub priority: Priority,
+    pub children: Vec<PromptPart>,
+}
+
+impl PromptPart {
+    /// A part grouping `children` under `label`, without content of its own
+    pub fn group(label: String, children: Vec<PromptPart>) -> Self {
+        PromptPart {
+            label,
+            content: String::new(),
+            priority: Priority::Description,
+            children,
+        }
+    }
+}
+
+/// Rough token count, about four characters per token for English and code
+pub fn estimate_tokens(text: &str) -> usize {
+    text.len().div_ceil(4)
+}
+
+/// A part with content, as the token budget sees it
+struct BudgetItem {
+    priority: Priority,
+    tokens: usize,
+    /// Index of the closest ancestor with content, e.g. the comment a reply
+    /// is nested under
+    parent: Option<usize>,
+}
+
+/// Each part with content, in the order they're rendered
+fn collect_budget_items(parts: &[PromptPart], parent: Option<usize>, items: &mut Vec<BudgetItem>) {
+    for part in parts {
+        let mut children_parent = parent;
+        if !part.content.is_empty() {
+            children_parent = Some(items.len());
+            items.push(BudgetItem {
+                priority: part.priority,
+                tokens: estimate_tokens(&part.content),
+                parent,
+            });
+        }
+        collect_budget_items(&part.children, children_parent, items);
+    }
+}
+
+/// Drops the parts not in `keep`, along with their children. Groups left
+/// without children are dropped as well. Counts what was dropped by priority.
+fn prune_parts(
+    parts: Vec<PromptPart>,
+    keep: &[bool],
+    next_item: &mut usize,
+    omitted: &mut Vec<(Priority, usize)>,
+) -> Vec<PromptPart> {
+    let mut kept_parts = Vec::new();
+
+    for mut part in parts {
+        let is_group = part.content.is_empty();
+        let kept = is_group || keep[*next_item];
+        if !is_group {
+            *next_item += 1;
+        }
+
+        let children = std::mem::take(&mut part.children);
+        if kept {
+            part.children = prune_parts(children, keep, next_item, omitted);
+            if !is_group || !part.children.is_empty() {
+                kept_parts.push(part);
+            }
+        } else {
+            // Replies make no sense without what they reply to
+            let mut dropped = Vec::new();
+            let mut descendants = Vec::new();
+            collect_budget_items(&children, None, &mut descendants);
+            *next_item += descendants.len();
+            dropped.push(part.priority);
+            dropped.extend(descendants.into_iter().map(|item| item.priority));
+
+            for priority in dropped {
+                match omitted.iter_mut().find(|(p, _)| *p == priority) {
+                    Some((_, count)) => *count += 1,
+                    None => omitted.push((priority, 1)),
+                }
+            }
+        }
+    }
+
+    kept_parts
+}
+
+/// Keeps the parts that fit in `token_budget`, handing out the budget by
+/// priority and keeping the original order for the parts that fit. A part
+/// ranks no higher than its parent and is only kept along with it, so no
+/// budget goes to replies whose comment was left out. What doesn't fit is
+/// collapsed into a note at the end. The first part is always kept.
+fn apply_token_budget(parts: Vec<PromptPart>, token_budget: usize) -> Vec<PromptPart> {
+    let mut items = Vec::new();
+    collect_budget_items(&parts, None, &mut items);
+
+    // Parents come before their children, so their rank is already known
+    let mut ranks: Vec<Priority> = Vec::with_capacity(items.len());
+    for item in &items {
+        let parent_rank = item.parent.map(|parent| ranks[parent]);
+        ranks.push(parent_rank.map_or(item.priority, |rank| rank.max(item.priority)));
+    }
+
+    let mut by_priority: Vec<usize> = (0..items.len()).collect();
+    by_priority.sort_by_key(|&i| (i != 0, ranks[i], i));
+
+    let mut keep = vec![false; items.len()];
+    let mut used = 0;
+    for i in by_priority {
+        let tokens = items[i].tokens;
+        let parent_kept = items[i].parent.is_none_or(|parent| keep[parent]);
+        if i == 0 || (parent_kept && used + tokens <= token_budget) {
+            keep[i] = true;
+            used += tokens;
+        }
+    }
+
+    let mut omitted: Vec<(Priority, usize)> = Vec::new();
+    let mut kept_parts = prune_parts(parts, &keep, &mut 0, &mut omitted);
+
+    if !omitted.is_empty() {
+        omitted.sort();
+        let total: usize = omitted.iter().map(|(_, count)| count).sum();
+        let noun = if omitted
+            .iter()
+            .all(|(priority, _)| *priority != Priority::Description)
+        {
+            "comments"
+        } else {
+            "sections"
+        };
+        let details = omitted
+            .iter()
+            .map(|(priority, count)| format!("{} {}", count, priority.describe()))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let content = format!(
+            "\n{} more {} omitted to fit the token budget of {} ({}). Raise GITHUB_CONTEXT_TOKEN_BUDGET to include them, or read them on GitHub.\n",
+            total, noun, token_budget, details
+        );
+        kept_parts.push(PromptPart {
+            label: format!("{} more {} omitted", total, noun),
+            content,
+            priority: Priority::Description,
+            children: Vec::new(),
+        });
+    }
+
+    kept_parts
 }
 
 pub fn build_slash_command_output(
     parts: Vec<PromptPart>,
+    token_budget: usize,
 ) -> (String, Vec<zed::SlashCommandOutputSection>) {
+    let parts = apply_token_budget(parts, token_budget);
+
     let mut sections = Vec::new();
     let mut text = String::new();
-    let mut current_position = 0;
 
     for (i, part) in parts.iter().enumerate() {
-        sections.push(zed::SlashCommandOutputSection {
-            range: (current_position..(current_position + part.length)).into(),
-            label: part.label.clone(),
-        });
+        // Add newlines between sections, outside of their ranges
+        if i > 0 {
+            text.push_str("\n\n");
+        }
+        render_part(part, &mut text, &mut sections);
+    }
 
-        text.push_str(&part.content);
-        current_position += part.length;
+    (text, sections)
+}
+
+/// Appends a part and its children to `text`. Sections are listed parents
+/// first, each covering the text of its children.
+fn render_part(
+    part: &PromptPart,
+    text: &mut String,
+    sections: &mut Vec<zed::SlashCommandOutputSection>,
+) {
+    let start = text.len();
+    let index = sections.len();
 
-        // Add newlines after each section except the last one
-        if i < parts.len() - 1 {
+    text.push_str(&part.content);
+    for child in &part.children {
+        if text.len() > start {
             text.push_str("\n\n");
-            current_position += 2; // Increment position but don't include in range
         }
+        render_part(child, text, sections);
     }
 
-    (text, sections)
+    sections.insert(
+        index,
+        zed::SlashCommandOutputSection {
+            range: (start..text.len()).into(),
+            label: part.label.clone(),
+        },
+    );
+}
+
+/// Keeps the first `max_lines` lines of `text`, noting how many were dropped.
+pub fn truncate_lines(text: &str, max_lines: usize) -> String {
+    let total = text.lines().count();
+    if total <= max_lines {
+        return text.to_string();
+    }
+
+    let mut truncated = text.lines().take(max_lines).collect::<Vec<_>>().join("\n");
+    truncated.push_str(&format!(
+        "\n... ({} more lines truncated)",
+        total - max_lines
+    ));
+    truncated
+}
+
+/// Code fence language for a file, from its extension or well known name
+pub fn fence_language(path: &str) -> &'static str {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    match name {
+        "Dockerfile" => return "dockerfile",
+        "Makefile" | "makefile" | "GNUmakefile" => return "make",
+        "CMakeLists.txt" => return "cmake",
+        _ => {}
+    }
+
+    let Some((_, extension)) = name.rsplit_once('.') else {
+        return "";
+    };
+    match extension.to_ascii_lowercase().as_str() {
+        "rs" => "rust",
+        "py" | "pyi" => "python",
+        "js" | "mjs" | "cjs" => "javascript",
+        "jsx" => "jsx",
+        "ts" | "mts" | "cts" => "typescript",
+        "tsx" => "tsx",
+        "go" => "go",
+        "java" => "java",
+        "kt" | "kts" => "kotlin",
+        "swift" => "swift",
+        "c" | "h" => "c",
+        "cc" | "cpp" | "cxx" | "hh" | "hpp" | "hxx" => "cpp",
+        "cs" => "csharp",
+        "rb" => "ruby",
+        "php" => "php",
+        "scala" => "scala",
+        "ex" | "exs" => "elixir",
+        "erl" | "hrl" => "erlang",
+        "hs" => "haskell",
+        "lua" => "lua",
+        "zig" => "zig",
+        "dart" => "dart",
+        "sh" | "bash" | "zsh" => "bash",
+        "fish" => "fish",
+        "ps1" => "powershell",
+        "sql" => "sql",
+        "html" | "htm" => "html",
+        "css" => "css",
+        "scss" => "scss",
+        "vue" => "vue",
+        "svelte" => "svelte",
+        "json" => "json",
+        "jsonc" => "jsonc",
+        "yaml" | "yml" => "yaml",
+        "toml" => "toml",
+        "xml" => "xml",
+        "md" | "markdown" => "markdown",
+        "proto" => "proto",
+        "graphql" | "gql" => "graphql",
+        "nix" => "nix",
+        "tf" => "hcl",
+        "diff" | "patch" => "diff",
+        _ => "",
+    }
+}
+
+/// Seconds since the unix epoch for a GitHub timestamp (`2024-01-31T12:00:00Z`).
+/// GitHub returns timestamps in UTC, other offsets aren't supported.
+fn parse_timestamp(timestamp: &str) -> Option<i64> {
+    let (date, time) = timestamp.strip_suffix('Z')?.split_once('T')?;
+    let mut date_parts = date.splitn(3, '-').map(|p| p.parse::<i64>().ok());
+    let (year, month, day) = (
+        date_parts.next()??,
+        date_parts.next()??,
+        date_parts.next()??,
+    );
+    let mut time_parts = time.splitn(3, ':').map(|p| p.get(..2)?.parse::<i64>().ok());
+    let (hour, minute, second) = (
+        time_parts.next()??,
+        time_parts.next()??,
+        time_parts.next()??,
+    );
+
+    // Days from civil date, see http://howardhinnant.github.io/date_algorithms.html
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+
+    Some(days * 86400 + hour * 3600 + minute * 60 + second)
+}
+
+/// Human readable age of a GitHub timestamp, e.g. "3 days"
+pub fn format_age(timestamp: &str) -> String {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default();
+
+    let Some(seconds) = parse_timestamp(timestamp).map(|then| (now - then).max(0)) else {
+        return timestamp.to_string();
+    };
+
+    let (value, unit) = match seconds {
+        s if s < 3600 => (s / 60, "minute"),
+        s if s < 86400 => (s / 3600, "hour"),
+        s if s < 86400 * 30 => (s / 86400, "day"),
+        s if s < 86400 * 365 => (s / (86400 * 30), "month"),
+        s => (s / (86400 * 365), "year"),
+    };
+
+    format!("{} {}{}", value, unit, if value == 1 { "" } else { "s" })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn part(label: &str, content: &str, children: Vec<PromptPart>) -> PromptPart {
+        PromptPart {
+            label: label.to_string(),
+            content: content.to_string(),
+            priority: Priority::UnresolvedThread,
+            children,
+        }
+    }
+
+    fn sample_parts() -> Vec<PromptPart> {
+        let thread = PromptPart::group(
+            "Thread at L3".to_string(),
+            vec![part(
+                "Comment",
+                "root",
+                vec![
+                    part("Reply 1", "reply 1", Vec::new()),
+                    part("Reply 2", "reply 2", Vec::new()),
+                ],
+            )],
+        );
+        vec![
+            part("PR", "description", Vec::new()),
+            PromptPart::group(
+                "Review comments".to_string(),
+                vec![PromptPart::group("src/main.rs".to_string(), vec![thread])],
+            ),
+            part("Checks", "checks", Vec::new()),
+        ]
+    }
+
+    #[test]
+    fn nested_sections_cover_their_content_and_children() {
+        let (text, sections) = build_slash_command_output(sample_parts(), 10_000);
+
+        // (label, covered text), parents listed before their children
+        let expected = [
+            ("PR", "description"),
+            ("Review comments", "root\n\nreply 1\n\nreply 2"),
+            ("src/main.rs", "root\n\nreply 1\n\nreply 2"),
+            ("Thread at L3", "root\n\nreply 1\n\nreply 2"),
+            ("Comment", "root\n\nreply 1\n\nreply 2"),
+            ("Reply 1", "reply 1"),
+            ("Reply 2", "reply 2"),
+            ("Checks", "checks"),
+        ];
+        let covered: Vec<(&str, &str)> = sections
+            .iter()
+            .map(|section| {
+                let range = section.range.start as usize..section.range.end as usize;
+                (section.label.as_str(), &text[range])
+            })
+            .collect();
+        assert_eq!(covered, expected);
+    }
+
+    #[test]
+    fn top_level_separators_are_outside_of_sections() {
+        let (text, sections) = build_slash_command_output(sample_parts(), 10_000);
+
+        let top_level: Vec<_> = sections
+            .iter()
+            .filter(|section| ["PR", "Review comments", "Checks"].contains(&section.label.as_str()))
+            .collect();
+        for pair in top_level.windows(2) {
+            let (previous, next) = (&pair[0].range, &pair[1].range);
+            assert_eq!(&text[previous.end as usize..next.start as usize], "\n\n");
+            for section in &sections {
+                let (start, end) = (section.range.start, section.range.end);
+                assert!(
+                    end <= previous.end || start >= next.start,
+                    "{}",
+                    section.label
+                );
+            }
+        }
+        assert_eq!(top_level.first().unwrap().range.start, 0);
+        assert_eq!(top_level.last().unwrap().range.end as usize, text.len());
+    }
+
+    #[test]
+    fn budget_keeps_replies_only_with_their_comment() {
+        let reply = PromptPart {
+            priority: Priority::UnresolvedThread,
+            ..part("Reply", "short reply", Vec::new())
+        };
+        let comment = PromptPart {
+            priority: Priority::Bot,
+            ..part("Comment", &"long bot comment ".repeat(20), vec![reply])
+        };
+        let parts = vec![
+            part("PR", "description", Vec::new()),
+            comment,
+            PromptPart {
+                priority: Priority::Review,
+                ..part("Review", "review", Vec::new())
+            },
+        ];
+
+        // Room for everything but the bot comment
+        let parts = apply_token_budget(parts, 20);
+        let labels: Vec<&str> = parts.iter().map(|part| part.label.as_str()).collect();
+        assert_eq!(labels, ["PR", "Review", "2 more comments omitted"]);
+        assert!(parts[2]
+            .content
+            .contains("1 unresolved thread comments, 1 bot comments"));
+    }
+
+    #[test]
+    fn parses_utc_timestamps() {
+        let cases = [
+            ("1970-01-01T00:00:00Z", 0),
+            ("1969-12-31T23:59:59Z", -1),
+            ("2024-01-31T12:00:00Z", 1706702400),
+            // leap days, including centuries divisible by 400
+            ("2024-02-29T00:00:00Z", 1709164800),
+            ("2024-03-01T00:00:00Z", 1709251200),
+            ("2000-02-29T23:59:59Z", 951868799),
+            ("2023-03-01T00:00:00Z", 1677628800),
+            ("2100-03-01T00:00:00Z", 4107542400),
+        ];
+
+        for (timestamp, expected) in cases {
+            assert_eq!(parse_timestamp(timestamp), Some(expected), "{}", timestamp);
+        }
+    }
+
+    #[test]
+    fn rejects_timestamps_not_in_utc() {
+        for timestamp in [
+            "2024-01-31T12:00:00+02:00",
+            "2024-01-31T12:00:00",
+            "2024-01-31",
+            "",
+        ] {
+            assert_eq!(parse_timestamp(timestamp), None, "{}", timestamp);
+        }
+    }
 }
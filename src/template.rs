@@ -0,0 +1,151 @@
+// Output templates with `{placeholder}` fields, chosen from presets or set by the user
+use std::str::FromStr;
+
+/// Built-in sets of templates
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    Default,
+    /// One line per item, without diff hunks or code
+    Compact,
+    /// Markdown with headers and links
+    Verbose,
+}
+
+impl FromStr for Preset {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "default" => Ok(Preset::Default),
+            "compact" => Ok(Preset::Compact),
+            "verbose" => Ok(Preset::Verbose),
+            _ => Err(format!("Unknown template preset: {}", value)),
+        }
+    }
+}
+
+/// Templates for the parts of `/pr-current` and `/pr-link` output
+#[derive(Debug, Clone)]
+pub struct Templates {
+    /// Fields: number, title, state, author, head, base, url, created, body
+    pub pull_request: String,
+    /// Fields: author, verdict, state, url, submitted, body
+    pub review: String,
+    /// Fields: author, location, worktree_location, url, diff, commented_code,
+    /// current_code, stale_note, body, suggestions
+    pub comment: String,
+    /// Fields: location, path, line, comments. In PR output the comments are
+    /// sections of their own, so the text before `{comments}` heads the
+    /// thread's first comment and the text after it follows the last reply.
+    pub thread: String,
+}
+
+impl Templates {
+    pub fn preset(preset: Preset) -> Self {
+        let (pull_request, review, comment, thread) = match preset {
+            Preset::Default => (
+                "\nPR #{number}: {title} [{state}]\n{head} into {base}\n\n{body}\n",
+                "\nReview from user: {author} ({verdict})\n{body}\n",
+                "\nComment from user: {author}\nOn: {location}\n{worktree_location}```diff\n{diff}\n```\n{commented_code}{current_code}{stale_note}\n{body}\n{suggestions}",
+                "\nThread on {location}\n{comments}",
+            ),
+            Preset::Compact => (
+                "\nPR #{number}: {title} [{state}] by @{author}, {head} into {base}\n{body}\n",
+                "\n@{author} {verdict}: {body}\n",
+                "\n@{author} on {location}: {body}\n{suggestions}",
+                "\n{location}:\n{comments}",
+            ),
+            Preset::Verbose => (
+                "\n# PR #{number}: {title}\n\n- State: {state}\n- Author: @{author}\n- Branches: {head} into {base}\n- Opened: {created}\n- Link: {url}\n\n## Description\n\n{body}\n",
+                "\n## Review by @{author}: {verdict}\n\n- Submitted: {submitted}\n- Link: {url}\n\n{body}\n",
+                "\n## Comment by @{author} on {location}\n\n- Link: {url}\n{worktree_location}\n```diff\n{diff}\n```\n\n{commented_code}{current_code}{stale_note}\n{body}\n\n{suggestions}",
+                "\n### Thread on {location}\n\n{comments}",
+            ),
+        };
+
+        Templates {
+            pull_request: pull_request.to_string(),
+            review: review.to_string(),
+            comment: comment.to_string(),
+            thread: thread.to_string(),
+        }
+    }
+}
+
+/// Replaces `{name}` placeholders with their values. Unknown placeholders
+/// are kept as they are, and values are not searched for placeholders.
+pub fn render(template: &str, values: &[(&str, &str)]) -> String {
+    let mut result = String::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        let placeholder = rest[start + 1..]
+            .find('}')
+            .map(|end| &rest[start + 1..start + 1 + end]);
+        match placeholder.and_then(|name| values.iter().find(|(key, _)| *key == name)) {
+            Some((name, value)) => {
+                result.push_str(value);
+                rest = &rest[start + name.len() + 2..];
+            }
+            None => {
+                result.push('{');
+                rest = &rest[start + 1..];
+            }
+        }
+    }
+    result.push_str(rest);
+
+    result
+}
+
+/// Templates set in environment variables can't always hold newlines, so
+/// `\n` is accepted in their place
+pub fn unescape(template: &str) -> String {
+    template.replace("\\n", "\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_placeholders() {
+        let values = [
+            ("author", "octocat"),
+            ("body", "Looks {good}"),
+            ("line", "12"),
+        ];
+        let cases = [
+            ("@{author}: {body}", "@octocat: Looks {good}"),
+            ("{author}{line}", "octocat12"),
+            ("{{author}}", "{octocat}"),
+            // Unknown placeholders are kept
+            ("{author} on {location}", "octocat on {location}"),
+            ("{}", "{}"),
+            // Values are not searched for placeholders
+            ("{body}", "Looks {good}"),
+            // Braces without a closing brace are kept
+            ("{author", "{author"),
+            ("fn main() { {line}", "fn main() { 12"),
+            ("{line} }", "12 }"),
+            ("", ""),
+        ];
+
+        for (template, expected) in cases {
+            assert_eq!(render(template, &values), expected, "{}", template);
+        }
+    }
+
+    #[test]
+    fn renders_values_holding_placeholder_names() {
+        let values = [("body", "{author}"), ("author", "octocat")];
+        assert_eq!(render("{body} by {author}", &values), "{author} by octocat");
+    }
+
+    #[test]
+    fn unescapes_newlines() {
+        assert_eq!(unescape("a\\nb\\n"), "a\nb\n");
+        assert_eq!(unescape("a\nb"), "a\nb");
+    }
+}